@@ -1,60 +1,130 @@
-use clap::{App, SubCommand, Arg, ArgMatches};
+use clap::{App, Arg, ArgMatches};
+use std::ffi::{OsStr, OsString};
 use std::io;
 use std::fs::File;
-use std::io::BufRead;
+use std::io::{Read, Write};
+use std::mem::MaybeUninit;
+use std::os::unix::io::{AsRawFd, RawFd};
+use crate::core::applet::Applet;
+use crate::core::error::{AppletError, report, EXIT_FAILURE};
+use crate::librb::copy::{copy_buffered, copy_fd, CopyError, CHUNK};
+use crate::librb::quote::escape_invalid_utf8;
 
-pub fn subcommand() -> App<'static, 'static>  {
-    SubCommand::with_name("cat")
-        .about("concate FILEs to stdout")
-        .arg(
+pub struct Cat;
+
+impl Applet for Cat {
+    fn name(&self) -> &'static str { "cat" }
+    fn usage(&self) -> &'static str { "concate FILEs to stdout" }
+
+    fn args(&self, cmd: App<'static, 'static>) -> App<'static, 'static> {
+        cmd.arg(
             Arg::with_name("number-lines").short("-n").help("number output lines").overrides_with("number-none-empty-lines")
         )
         .arg(
             Arg::with_name("number-none-empty-lines").short("-b").help("number none-empty output lines").overrides_with("number-lines")
         )
+        .arg(
+            Arg::with_name("show-all").short("-A").long("--show-all").help("equivalent to -vET")
+        )
+        .arg(
+            Arg::with_name("nonprinting-ends").short("-e").help("equivalent to -vE")
+        )
+        .arg(
+            Arg::with_name("show-ends").short("-E").long("--show-ends").help("display $ at end of each line")
+        )
+        .arg(
+            Arg::with_name("squeeze-blank").short("-s").long("--squeeze-blank").help("suppress repeated empty output lines")
+        )
+        .arg(
+            Arg::with_name("nonprinting-tabs").short("-t").help("equivalent to -vT")
+        )
+        .arg(
+            Arg::with_name("show-tabs").short("-T").long("--show-tabs").help("display TAB characters as ^I")
+        )
+        .arg(
+            Arg::with_name("show-nonprinting").short("-v").long("--show-nonprinting")
+                .help("use ^ and M- notation, except for LFD and TAB")
+        )
         .arg(
             Arg::with_name("files").multiple(true).index(1)
         )
-}
-struct FullLines<B> {
-    buf: B,
-}
+    }
 
-impl<B: BufRead> Iterator for FullLines<B> {
-    type Item = io::Result<String>;
+    fn main(&self, args: &ArgMatches, stdin: &mut dyn Read, stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), AppletError> {
+        _cat_main(Some(args), stdin, stdout, stderr, None)
+    }
 
-    fn next(&mut self) -> Option<io::Result<String>> {
-        let mut buf = String::new();
-        match self.buf.read_line(&mut buf) {
-            Ok(0) => None,
-            Ok(_n) => {
-                Some(Ok(buf))
-            }
-            Err(e) => Some(Err(e)),
+    fn main_with_fds(&self, args: &ArgMatches, stdin: &mut dyn Read, stdout: &mut dyn Write, stderr: &mut dyn Write,
+                     fds: (RawFd, RawFd)) -> Result<(), AppletError> {
+        _cat_main(Some(args), stdin, stdout, stderr, Some(fds))
+    }
+}
+
+/// The status of an open file
+fn fstat(fd: RawFd) -> Option<libc::stat> {
+    let mut buf = MaybeUninit::<libc::stat>::uninit();
+    // fstat only writes to the buffer it is given, which is initialized when it succeeds
+    unsafe {
+        if libc::fstat(fd, buf.as_mut_ptr()) != 0 {
+            return None;
         }
+        Some(buf.assume_init())
     }
 }
 
-fn get_reader(filename: &String) -> io::Result<Box<dyn BufRead>> {
-    if filename == "-" {
-        Ok(Box::new(io::BufReader::new(io::stdin())))
-    } else {
-        let f = File::open(filename)?;
-        let reader = io::BufReader::new(f);
-        Ok(Box::new(reader))
+/// Whether `input` is the regular file `output` with something left to read, like coreutils checks.
+/// Copying it would go on as long as there is disk space.
+fn is_output(input: RawFd, output: RawFd) -> bool {
+    match (fstat(input), fstat(output)) {
+        (Some(input_stat), Some(output_stat)) => {
+            output_stat.st_mode & libc::S_IFMT == libc::S_IFREG
+                && (input_stat.st_dev, input_stat.st_ino) == (output_stat.st_dev, output_stat.st_ino)
+                && unsafe { libc::lseek(input, 0, libc::SEEK_CUR) } < input_stat.st_size
+        }
+        _ => false,
     }
 }
 
-fn _output_file(name: &String, fmt: &mut DisplayFormat, writer: &mut impl std::io::Write) -> Result<(), io::Error>{
-    let reader = get_reader(name)?;
-    let lines_reader = FullLines { buf: reader };
-    for line in lines_reader {
-        if let Ok(line) = line {
-            fmt.write_line(line, writer)?;
+/// Copy or format the file `name`, stdin for -. The kernel copies when `fds` are the descriptors behind `stdin`
+/// and `writer`.
+fn cat_file(name: &OsStr, fmt: &mut DisplayFormat, stdin: &mut dyn Read, writer: &mut dyn Write, fds: Option<(RawFd, RawFd)>) -> Result<(), CopyError> {
+    let mut file = if name == "-" { None } else { Some(File::open(name).map_err(CopyError::Read)?) };
+    if let Some((stdin_fd, stdout_fd)) = fds {
+        let input_fd = file.as_ref().map_or(stdin_fd, File::as_raw_fd);
+        if is_output(input_fd, stdout_fd) {
+            return Err(CopyError::Read(io::Error::other("input file is output file")));
+        }
+        if fmt.is_plain() {
+            // Whatever went through the writer comes first
+            writer.flush().map_err(CopyError::Write)?;
+            copy_fd(input_fd, stdout_fd)?;
+            return Ok(());
         }
-        // TODO: Handle errors
     }
-    Ok(())
+    let reader: &mut dyn Read = match &mut file {
+        Some(file) => file,
+        None => stdin,
+    };
+    if fmt.is_plain() {
+        copy_buffered(reader, writer)?;
+        return Ok(());
+    }
+    // A chunk at a time cut at its newlines, so a long line doesn't have to fit in memory. What a line was like
+    // so far carries over to the next chunk as it does to the next file.
+    let (mut buf, mut scratch) = (vec![0; CHUNK], Vec::new());
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(CopyError::Read(e)),
+        };
+        for line in buf[..n].split_inclusive(|c| *c == b'\n') {
+            fmt.write_line(line, &mut scratch, writer).map_err(CopyError::Write)?;
+        }
+    }
+    // Like coreutils, a file is written out before the next one is opened, and a failure to is reported first
+    writer.flush().map_err(CopyError::Write)
 }
 
 #[derive(PartialEq)]
@@ -66,73 +136,201 @@ enum NumberLineOption {
 
 struct DisplayFormat {
     line_numbers: NumberLineOption,
-    current_line: u32,
+    current_line: u64,
+    /// Control characters as ^X and bytes above 127 as M-, -v
+    show_nonprinting: bool,
+    /// $ before each newline, -E
+    show_ends: bool,
+    /// Tabs as ^I, -T
+    show_tabs: bool,
+    /// One empty line for each run of them, -s
+    squeeze_blank: bool,
+    /// Whether the last line was empty, runs go on across files
+    after_blank: bool,
+    /// A chunk or file ended without a newline, the next one goes on with the same line
+    mid_line: bool,
+    /// A CR ended the line so far, with -E it shows as ^M if the newline comes next
+    pending_cr: bool,
+}
+
+/// `line` without its newline, with what -v and -T make visible spelled out like coreutils
+fn make_visible(line: &[u8], nonprinting: bool, tabs: bool, out: &mut Vec<u8>) {
+    for &c in line {
+        match c {
+            b'\t' if !tabs => out.push(c),
+            _ if !nonprinting && c != b'\t' => out.push(c),
+            _ => {
+                let c = if c >= 128 {
+                    out.extend(b"M-");
+                    c - 128
+                } else {
+                    c
+                };
+                match c {
+                    0..=31 => out.extend(&[b'^', c + 64]),
+                    127 => out.extend(b"^?"),
+                    _ => out.push(c),
+                }
+            }
+        }
+    }
 }
 
 impl DisplayFormat {
     fn build(matches: &Option<&ArgMatches>) -> DisplayFormat {
         if let Some(matches) = matches {
+            let any = |flags: &[&str]| flags.iter().any(|flag| matches.is_present(flag));
             DisplayFormat {
                 line_numbers: match (matches.is_present("number-lines"), matches.is_present("number-none-empty-lines")) {
                     (true, _) => NumberLineOption::All,
                     (false, true) => NumberLineOption::OnlyNoneEmpty,
                     (false, false) => NumberLineOption::None,
                 },
-                current_line: 1
+                current_line: 1,
+                show_nonprinting: any(&["show-nonprinting", "show-all", "nonprinting-ends", "nonprinting-tabs"]),
+                show_ends: any(&["show-ends", "show-all", "nonprinting-ends"]),
+                show_tabs: any(&["show-tabs", "show-all", "nonprinting-tabs"]),
+                squeeze_blank: matches.is_present("squeeze-blank"),
+                after_blank: false,
+                mid_line: false,
+                pending_cr: false,
             }
         } else {
             DisplayFormat {
                 line_numbers: NumberLineOption::None,
-                current_line: 1
+                current_line: 1,
+                show_nonprinting: false,
+                show_ends: false,
+                show_tabs: false,
+                squeeze_blank: false,
+                after_blank: false,
+                mid_line: false,
+                pending_cr: false,
             }
         }
     }
-    fn write_line(&mut self, line: String, writer: &mut dyn std::io::Write) -> Result<(), io::Error> {
-        if self.line_numbers == NumberLineOption::All || (self.line_numbers == NumberLineOption::OnlyNoneEmpty && !line.trim_end_matches("\n").is_empty()) {
-            write!(writer, "{n:>6}  {line}", n=self.current_line, line=line)?;
+    /// Without options the files are just copied
+    fn is_plain(&self) -> bool {
+        self.line_numbers == NumberLineOption::None && !self.show_nonprinting && !self.show_ends && !self.show_tabs && !self.squeeze_blank
+    }
+
+    /// Write `line`, which is a whole line or the part of one a chunk has. `scratch` is for the visible form of the
+    /// line, so it isn't allocated for each one.
+    fn write_line(&mut self, line: &[u8], scratch: &mut Vec<u8>, writer: &mut dyn std::io::Write) -> Result<(), io::Error> {
+        let starts_line = !self.mid_line;
+        let blank = starts_line && line == b"\n";
+        self.mid_line = !line.ends_with(b"\n");
+        if self.squeeze_blank && blank && self.after_blank {
+            return Ok(());
+        }
+        self.after_blank = blank;
+        if starts_line && (self.line_numbers == NumberLineOption::All || (self.line_numbers == NumberLineOption::OnlyNoneEmpty && !blank)) {
+            write!(writer, "{n:>6}  ", n=self.current_line)?;
             self.current_line += 1;
-            Ok(())
-        } else {
-            write!(writer, "{}", line)
         }
+        if !self.show_nonprinting && !self.show_ends && !self.show_tabs {
+            return writer.write_all(line);
+        }
+        let (content, newline) = match line.strip_suffix(b"\n") {
+            Some(content) => (content, true),
+            None => (line, false),
+        };
+        scratch.clear();
+        if self.pending_cr {
+            scratch.push(b'\r');
+            self.pending_cr = false;
+        }
+        make_visible(content, self.show_nonprinting, self.show_tabs, scratch);
+        if !newline && self.show_ends && scratch.last() == Some(&b'\r') {
+            // It is ^M$ if the line ends right after it, only the next chunk or file tells
+            scratch.pop();
+            self.pending_cr = true;
+        }
+        if newline && self.show_ends {
+            // Like coreutils, the CR of a CRLF line ending shows as well
+            if scratch.last() == Some(&b'\r') {
+                scratch.pop();
+                scratch.extend(b"^M");
+            }
+            scratch.push(b'$');
+        }
+        if newline {
+            scratch.push(b'\n');
+        }
+        writer.write_all(scratch)
+    }
+
+    /// What is left of the last line once all files are written
+    fn finish(&mut self, writer: &mut dyn Write) -> Result<(), io::Error> {
+        if self.pending_cr {
+            self.pending_cr = false;
+            writer.write_all(b"\r")?;
+        }
+        Ok(())
     }
 }
 
-fn get_files(matches: &Option<&ArgMatches>) -> Vec<String> {
+fn get_files(matches: &Option<&ArgMatches>) -> Vec<OsString> {
     if let Some(matches) = matches {
-        if let Some(files) = matches.values_of("files") {
-            files.map(|res| res.to_string()).collect()
+        if let Some(files) = matches.values_of_os("files") {
+            files.map(|res| res.to_os_string()).collect()
         } else {
-            vec!["-".to_string()]
+            vec![OsString::from("-")]
         }
     } else {
-        vec!["-".to_string()]
+        vec![OsString::from("-")]
     }
 }
 
-fn _cat_main(matches: Option<&ArgMatches>, writer: &mut impl std::io::Write) -> Result<(), String> {
+/// `fds` are the descriptors behind `stdin` and `writer` when the kernel may copy between them directly
+/// Files that can't be read are reported and skipped, failing to write ends the run
+fn _cat_main(matches: Option<&ArgMatches>, stdin: &mut dyn Read, writer: &mut dyn Write, stderr: &mut dyn Write,
+             fds: Option<(RawFd, RawFd)>) -> Result<(), AppletError> {
     let mut fmt = DisplayFormat::build(&matches);
     let files = get_files(&matches);
+    // Formatted lines are gathered, a line buffered stdout would write them one at a time
+    let mut buffered;
+    let writer: &mut dyn Write = match fmt.is_plain() {
+        true => writer,
+        false => {
+            buffered = io::BufWriter::with_capacity(CHUNK, writer);
+            &mut buffered
+        }
+    };
+    let mut failed = false;
     for filename in files {
-        match _output_file(&filename, &mut fmt, writer).err() {
-            Some(x) => {
-                return Err(format!("{file}: {err}", file=filename, err=x))
-            },
-            None => ()
+        match cat_file(&filename, &mut fmt, stdin, writer, fds) {
+            Ok(()) => (),
+            Err(CopyError::Read(e)) => {
+                report(stderr, Cat.name(), &AppletError::io(escape_invalid_utf8(&filename), &e));
+                failed = true;
+            }
+            // The other files would fail the same way, or nobody reads what they would give
+            Err(CopyError::Write(e)) => return Err(AppletError::io("write error", &e)),
         }
     }
+    fmt.finish(writer).and_then(|()| writer.flush()).map_err(|e| AppletError::io("write error", &e))?;
+    if failed {
+        return Err(AppletError::silent(EXIT_FAILURE));
+    }
     Ok(())
 }
 
-pub fn cat_main(matches: Option<&ArgMatches>) -> Result<(), String>{
-    _cat_main(matches, &mut std::io::stdout())
-}
-
 #[cfg(test)]
 mod tests {
     use std::ffi::OsStr;
-    use super::{subcommand, _cat_main};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use super::{Cat, _cat_main};
+    use crate::core::applet::Applet;
+    use crate::core::error::{AppletError, EXIT_FAILURE};
+    use crate::core::pipe::{BrokenPipeWriter, EXIT_BROKEN_PIPE};
+    use crate::core::test_support::setup_dir;
+    use crate::librb::copy::{binary, CHUNK};
+    use std::fs::{File, OpenOptions};
+    use std::os::unix::io::AsRawFd;
     use std::process::Command;
+    use std::time::Instant;
 
     fn create_file(name: &str, content: &str) {
         Command::new("sh")
@@ -144,10 +342,10 @@ mod tests {
 
     fn run_get_output(args: Vec<&OsStr>) -> Vec<u8> {
         let mut s : Vec<u8> = Vec::new();
-        let cmd = subcommand();
+        let cmd = Cat.subcommand();
         let matches = cmd.get_matches_from(args.iter());
-        _cat_main(Some(&matches), &mut s);
-        return s;
+        _cat_main(Some(&matches), &mut io::empty(), &mut s, &mut io::sink(), None).unwrap();
+        s
     }
 
     fn run_cat_test_case(name: &str, content: &str, args: Vec<&OsStr>, expected_output: &str) {
@@ -190,4 +388,201 @@ mod tests {
         let args = vec![OsStr::new("cat"), OsStr::new("-b"), OsStr::new(name)];
         run_cat_test_case(name, content, args, expected_output);
     }
+
+    #[test]
+    fn test_cat_non_utf8_file_name() {
+        let name = OsStr::from_bytes(b"/tmp/rustybox-cat-test-caf\xe9");
+        std::fs::write(name, "non utf-8 name\n").unwrap();
+        let args = vec![OsStr::new("cat"), name];
+        assert_eq!(run_get_output(args), b"non utf-8 name\n");
+
+        let missing = OsStr::from_bytes(b"/tmp/rustybox-cat-test-missing\xff");
+        let matches = Cat.subcommand().get_matches_from(vec![OsStr::new("cat"), missing]);
+        let mut stderr = Vec::new();
+        let err = _cat_main(Some(&matches), &mut io::empty(), &mut io::sink(), &mut stderr, None).unwrap_err();
+        assert_eq!(err, AppletError::silent(EXIT_FAILURE));
+        assert_eq!(stderr, b"rustybox: cat: /tmp/rustybox-cat-test-missing\\xff: No such file or directory\n");
+    }
+
+    #[test]
+    fn test_cat_continues_past_errors() {
+        let (a, b) = ("/tmp/rustybox-cat-test-continue-a", "/tmp/rustybox-cat-test-continue-b");
+        create_file(a, "a\n");
+        create_file(b, "b\n");
+        // Copied and formatted alike
+        for flags in [vec![], vec!["-n"]].iter() {
+            let operands = vec![a, "/tmp/rustybox-cat-test-missing", "/tmp", b];
+            let matches = Cat.subcommand().get_matches_from(["cat"].iter().chain(flags).chain(&operands));
+            let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+            let err = _cat_main(Some(&matches), &mut io::empty(), &mut stdout, &mut stderr, None).unwrap_err();
+            assert_eq!(err, AppletError::silent(EXIT_FAILURE));
+            let expected: &[u8] = if flags.is_empty() { b"a\nb\n" } else { b"     1  a\n     2  b\n" };
+            assert_eq!(stdout, expected);
+            assert_eq!(String::from_utf8(stderr).unwrap(), "rustybox: cat: /tmp/rustybox-cat-test-missing: No such file or directory\n\
+                                                           rustybox: cat: /tmp: Is a directory\n");
+        }
+    }
+
+    #[test]
+    fn test_cat_write_error() {
+        let (a, b) = ("/tmp/rustybox-cat-test-write-error-a", "/tmp/rustybox-cat-test-write-error-b");
+        create_file(a, "a\n");
+        create_file(b, "b\n");
+        // Like cat a missing b > /dev/full, copied by the kernel, through a buffer and formatted. Writing a fails
+        // before missing is opened.
+        for (flags, by_fds) in [(vec![], true), (vec![], false), (vec!["-n"], true)].iter() {
+            let full = OpenOptions::new().write(true).open("/dev/full").unwrap();
+            let fds = if *by_fds { Some((0, full.as_raw_fd())) } else { None };
+            let operands = vec![a, "/tmp/rustybox-cat-test-missing", b];
+            let matches = Cat.subcommand().get_matches_from(["cat"].iter().chain(flags).chain(&operands));
+            let mut stderr = Vec::new();
+            let err = _cat_main(Some(&matches), &mut io::empty(), &mut &full, &mut stderr, fds).unwrap_err();
+            assert_eq!(err, AppletError::failure("write error: No space left on device".to_string()), "{:?} {}", flags, by_fds);
+            assert_eq!(stderr, b"");
+        }
+    }
+
+    #[test]
+    fn test_cat_input_is_output() {
+        let dir: &str = &setup_dir("test_cat_input_is_output");
+        let (other, out) = (format!("{}/other", dir), format!("{}/out", dir));
+        std::fs::write(&other, "other\n").unwrap();
+        for flags in [vec![], vec!["-n"]].iter() {
+            std::fs::write(&out, "out\n").unwrap();
+            // Like cat out other out >> out
+            let mut output = OpenOptions::new().append(true).open(&out).unwrap();
+            let fds = Some((0, output.as_raw_fd()));
+            let operands = vec![out.as_str(), &other, &out];
+            let matches = Cat.subcommand().get_matches_from(["cat"].iter().chain(flags).chain(&operands));
+            let mut stderr = Vec::new();
+            let err = _cat_main(Some(&matches), &mut io::empty(), &mut output, &mut stderr, fds).unwrap_err();
+            assert_eq!(err, AppletError::silent(EXIT_FAILURE));
+            let expected = if flags.is_empty() { "out\nother\n" } else { "out\n     1  other\n" };
+            assert_eq!(std::fs::read_to_string(&out).unwrap(), expected);
+            assert_eq!(String::from_utf8(stderr).unwrap(), format!("rustybox: cat: {0}: input file is output file\n\
+                                                                    rustybox: cat: {0}: input file is output file\n", out));
+        }
+
+        // Nothing is left to read of an empty output, like after cat out > out
+        std::fs::write(&out, "").unwrap();
+        let mut output = OpenOptions::new().write(true).open(&out).unwrap();
+        let fds = Some((0, output.as_raw_fd()));
+        let matches = Cat.subcommand().get_matches_from(vec!["cat", &out, &other]);
+        _cat_main(Some(&matches), &mut io::empty(), &mut output, &mut io::sink(), fds).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "other\n");
+    }
+
+    /// `data` numbered like -n, or -b with `blank_too` false
+    fn numbered(data: &[u8], blank_too: bool) -> Vec<u8> {
+        let mut expected = Vec::new();
+        let mut number = 1;
+        for line in data.split_inclusive(|c| *c == b'\n') {
+            if blank_too || line != b"\n" {
+                expected.extend(format!("{:>6}  ", number).bytes());
+                number += 1;
+            }
+            expected.extend(line);
+        }
+        expected
+    }
+
+    #[test]
+    fn test_cat_binary() {
+        let name = "/tmp/rustybox-cat-test-binary";
+        let data = binary();
+        std::fs::write(name, &data).unwrap();
+        assert_eq!(run_get_output(vec![OsStr::new("cat"), OsStr::new(name)]), data);
+        assert_eq!(run_get_output(vec![OsStr::new("cat"), OsStr::new("-n"), OsStr::new(name)]), numbered(&data, true));
+        assert_eq!(run_get_output(vec![OsStr::new("cat"), OsStr::new("-b"), OsStr::new(name)]), numbered(&data, false));
+    }
+
+    #[test]
+    fn test_cat_show_matches_coreutils() {
+        let name = "/tmp/rustybox-cat-test-show";
+        let mut data = b"tab\there\r\n\n\n\nctrl \x01\x1b del \x7f high \x80\x9b\xa0\xe9\xff\n\n\tno newline".to_vec();
+        data.extend(binary());
+        std::fs::write(name, &data).unwrap();
+        for flag in ["-A", "-v", "-E", "-T", "-s", "-e", "-t", "-vT", "-ET", "-sA"].iter() {
+            let expected = Command::new("sh").arg("-c").arg(format!("LC_ALL=C cat {flag} {name} {name}", flag=flag, name=name))
+                .output().expect("failed to execute process").stdout;
+            assert_eq!(run_get_output(vec![OsStr::new("cat"), OsStr::new(flag), OsStr::new(name), OsStr::new(name)]), expected, "{}", flag);
+        }
+    }
+
+    #[test]
+    fn test_cat_long_lines() {
+        let dir: &str = &setup_dir("test_cat_long_lines");
+        let (long, short) = (format!("{}/long", dir), format!("{}/short", dir));
+        // A CRLF split between two chunks, lines longer than a chunk, and CRs ending files
+        let mut data = vec![b'x'; CHUNK - 1];
+        data.extend(b"\r\n");
+        data.extend(vec![b'y'; 3 * CHUNK]);
+        data.extend(b"\n\n\n\na\r");
+        std::fs::write(&long, &data).unwrap();
+        std::fs::write(&short, b"\nb\r").unwrap();
+        for flag in ["-E", "-sA", "-vET"].iter() {
+            let expected = Command::new("sh").arg("-c").arg(format!("LC_ALL=C cat {} {long} {short} {long}", flag, long=long, short=short))
+                .output().expect("failed to execute process").stdout;
+            let args = [flag, long.as_str(), short.as_str(), long.as_str()];
+            assert!(run_get_output(std::iter::once("cat").chain(args.iter().copied()).map(OsStr::new).collect()) == expected, "{}", flag);
+        }
+        let numbered = run_get_output(vec![OsStr::new("cat"), OsStr::new("-n"), OsStr::new(&long), OsStr::new(&short), OsStr::new(&long)]);
+        assert!(numbered.starts_with(b"     1  xxx"));
+        assert!(numbered.ends_with(b"\n    12  a\r"));
+    }
+
+    #[test]
+    fn test_cat_squeeze_and_numbers() {
+        let name = "/tmp/rustybox-cat-test-squeeze";
+        // The second copy goes on with the line the first one ended in
+        create_file(name, "a\n\n\n\nb\n\nc");
+        let args = |flag| vec![OsStr::new("cat"), OsStr::new(flag), OsStr::new(name), OsStr::new(name)];
+        assert_eq!(run_get_output(args("-sn")), b"     1  a\n     2  \n     3  b\n     4  \n     5  ca\n     6  \n     7  b\n     8  \n     9  c");
+        assert_eq!(run_get_output(args("-sb")), b"     1  a\n\n     2  b\n\n     3  ca\n\n     4  b\n\n     5  c");
+    }
+
+    #[test]
+    fn test_cat_by_descriptors() {
+        let dir: &str = &setup_dir("test_cat_by_descriptors");
+        let data = binary();
+        std::fs::write(format!("{}/in", dir), &data).unwrap();
+        let mut stdin = File::open(format!("{}/in", dir)).unwrap();
+        let mut output = File::create(format!("{}/out", dir)).unwrap();
+        let fds = Some((stdin.as_raw_fd(), output.as_raw_fd()));
+
+        let input = format!("{}/in", dir);
+        let matches = Cat.subcommand().get_matches_from(vec!["cat", &input, "-", &input]);
+        _cat_main(Some(&matches), &mut stdin, &mut output, &mut io::sink(), fds).unwrap();
+        assert_eq!(std::fs::read(format!("{}/out", dir)).unwrap(), [&data[..], &data[..], &data[..]].concat());
+    }
+
+    /// How long copying a large file to /dev/null takes next to coreutils, run with
+    /// `cargo test --release test_cat_benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn test_cat_benchmark() {
+        let name = "/tmp/rustybox-cat-benchmark";
+        Command::new("sh").arg("-c").arg(format!("head -c 100000000 /dev/urandom | od -An -tx1 | head -c 200000000 > {}", name))
+            .output().expect("failed to execute process");
+        for args in [vec![], vec!["-n"]].iter() {
+            let start = Instant::now();
+            Command::new("sh").arg("-c").arg(format!("cat {} {} > /dev/null", args.join(" "), name)).status().unwrap();
+            let coreutils = start.elapsed();
+
+            let null = OpenOptions::new().write(true).open("/dev/null").unwrap();
+            let matches = Cat.subcommand().get_matches_from(vec!["cat"].into_iter().chain(args.iter().copied()).chain(Some(name)));
+            let start = Instant::now();
+            _cat_main(Some(&matches), &mut io::empty(), &mut &null, &mut io::sink(), Some((0, null.as_raw_fd()))).unwrap();
+            println!("cat {:?}: {:?}, coreutils {:?}", args, start.elapsed(), coreutils);
+        }
+    }
+
+    #[test]
+    fn test_cat_broken_pipe() {
+        let name = "/tmp/rustybox-cat-test-broken-pipe";
+        create_file(name, "nobody reads this");
+        let matches = Cat.subcommand().get_matches_from(vec![OsStr::new("cat"), OsStr::new(name)]);
+        let err = _cat_main(Some(&matches), &mut io::empty(), &mut BrokenPipeWriter, &mut io::sink(), None).unwrap_err();
+        assert_eq!(err, AppletError::silent(EXIT_BROKEN_PIPE));
+    }
 }
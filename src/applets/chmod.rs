@@ -0,0 +1,346 @@
+use clap::{App, AppSettings, Arg, ArgMatches, ErrorKind};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use crate::core::applet::Applet;
+use crate::core::error::{AppletError, report, EXIT_FAILURE};
+use crate::librb::file::mode::{current_umask, ModeExpr};
+use crate::librb::file::permissions::PermissionsMask;
+use crate::librb::quote::escape_invalid_utf8;
+
+pub struct Chmod;
+
+impl Applet for Chmod {
+    fn name(&self) -> &'static str { "chmod" }
+    fn usage(&self) -> &'static str { "Change the mode of each FILE to MODE" }
+
+    fn args(&self, cmd: App<'static, 'static>) -> App<'static, 'static> {
+        // Modes like -w look like flags, unknown flags are taken as operands instead (see get_matches)
+        cmd.setting(AppSettings::AllowLeadingHyphen).arg(
+            Arg::with_name("recursive").short("-R").long("--recursive").help("change files and directories recursively")
+        ).arg(
+            Arg::with_name("verbose").short("-v").long("--verbose").overrides_with("changes").help("output a diagnostic for every file processed")
+        ).arg(
+            Arg::with_name("changes").short("-c").long("--changes").overrides_with("verbose").help("like verbose but report only when a change is made")
+        ).arg(
+            Arg::with_name("reference").long("--reference").value_name("RFILE").takes_value(true).help("use RFILE's mode instead of a MODE")
+        ).arg(
+            Arg::with_name("mode").value_name("MODE").index(1).required(true)
+        ).arg(
+            Arg::with_name("files").value_name("FILE").multiple(true).index(2)
+        )
+    }
+
+    /// Of the operands that look like options, only modes are let through. The others are still unknown options,
+    /// unless they come after --.
+    fn get_matches(&self, args: Vec<OsString>) -> clap::Result<ArgMatches<'static>> {
+        let options: Vec<OsString> = args.iter().take_while(|arg| *arg != "--").cloned().collect();
+        let matches = self.subcommand().get_matches_from_safe(args)?;
+        let operands = matches.values_of_os("mode").into_iter().flatten().chain(matches.values_of_os("files").into_iter().flatten());
+        for operand in operands {
+            let is_mode = || operand.to_str().is_some_and(|mode| ModeExpr::parse(mode).is_ok());
+            if operand.len() > 1 && operand.as_bytes()[0] == b'-' && options.iter().any(|arg| arg == operand) && !is_mode() {
+                let message = format!("Found argument '{}' which wasn't expected, or isn't valid in this context",
+                                      escape_invalid_utf8(operand));
+                return Err(clap::Error::with_description(&message, ErrorKind::UnknownArgument));
+            }
+        }
+        Ok(matches)
+    }
+
+    fn main(&self, args: &ArgMatches, _stdin: &mut dyn Read, stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), AppletError> {
+        chmod_main(args, stdout, stderr)
+    }
+}
+
+enum NewMode {
+    Expr(ModeExpr),
+    Reference(PermissionsMask),
+}
+
+#[derive(PartialEq)]
+enum Verbosity {
+    Quiet,
+    Changes,
+    All,
+}
+
+struct ChmodRun<'a> {
+    mode: NewMode,
+    recursive: bool,
+    verbosity: Verbosity,
+    umask: u32,
+    /// A MODE given like options, `chmod -w`, is expected to do what it says: like coreutils, it is reported when
+    /// the umask kept bits set that it would have cleared
+    check_umask: bool,
+    failed: bool,
+    stdout: &'a mut dyn Write,
+    stderr: &'a mut dyn Write,
+}
+
+impl ChmodRun<'_> {
+    fn fail(&mut self, err: AppletError) {
+        report(self.stderr, Chmod.name(), &err);
+        self.failed = true;
+    }
+
+    fn describe(&mut self, path: &Path, old: PermissionsMask, new: PermissionsMask) -> io::Result<()> {
+        let name = escape_invalid_utf8(path.as_os_str());
+        if old != new && self.verbosity != Verbosity::Quiet {
+            writeln!(self.stdout, "mode of '{}' changed from {:04o} ({}) to {:04o} ({})", name, old, old, new, new)?;
+        } else if old == new && self.verbosity == Verbosity::All {
+            writeln!(self.stdout, "mode of '{}' retained as {:04o} ({})", name, old, old)?;
+        }
+        Ok(())
+    }
+
+    /// Operands are followed when they are symlinks, links met while recursing are skipped like coreutils does.
+    /// Only failing to write the verbose output is an error, everything else is reported and skipped.
+    fn chmod(&mut self, path: &Path, is_operand: bool) -> io::Result<()> {
+        let name = escape_invalid_utf8(path.as_os_str());
+        let meta = match if is_operand { fs::metadata(path) } else { fs::symlink_metadata(path) } {
+            Ok(meta) => meta,
+            Err(e) => {
+                self.fail(AppletError::io(format!("cannot access '{}'", name), &e));
+                return Ok(());
+            }
+        };
+        if meta.file_type().is_symlink() {
+            return Ok(());
+        }
+        let old = PermissionsMask::build(meta.mode());
+        let new = match &self.mode {
+            NewMode::Expr(expr) => expr.apply(old, meta.is_dir(), self.umask),
+            NewMode::Reference(mode) => *mode,
+        };
+        if new != old {
+            if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(new.bits())) {
+                self.fail(AppletError::io(format!("changing permissions of '{}'", name), &e));
+                return Ok(());
+            }
+        }
+        self.describe(path, old, new)?;
+        if let NewMode::Expr(expr) = &self.mode {
+            let expected = expr.apply(old, meta.is_dir(), 0);
+            if self.check_umask && new.bits() & !expected.bits() != 0 {
+                self.fail(AppletError::failure(format!("{}: new permissions are {}, not {}", name, new, expected)));
+            }
+        }
+
+        if self.recursive && meta.is_dir() {
+            let dir = match fs::read_dir(path) {
+                Ok(dir) => dir,
+                Err(e) => {
+                    self.fail(AppletError::io(format!("cannot read directory '{}'", name), &e));
+                    return Ok(());
+                }
+            };
+            for entry in dir {
+                match entry {
+                    Ok(entry) => self.chmod(&entry.path(), false)?,
+                    Err(e) => self.fail(AppletError::io(format!("cannot read directory '{}'", name), &e)),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_mode(mode: &OsStr) -> Result<NewMode, AppletError> {
+    let invalid = || AppletError::failure(format!("invalid mode: '{}'", escape_invalid_utf8(mode)));
+    let expr = ModeExpr::parse(mode.to_str().ok_or_else(invalid)?).map_err(AppletError::failure)?;
+    Ok(NewMode::Expr(expr))
+}
+
+fn reference_mode(reference: &OsStr) -> Result<NewMode, AppletError> {
+    let meta = fs::metadata(reference)
+        .map_err(|e| AppletError::io(format!("failed to get attributes of '{}'", escape_invalid_utf8(reference)), &e))?;
+    Ok(NewMode::Reference(PermissionsMask::build(meta.mode())))
+}
+
+fn chmod_main(args: &ArgMatches, stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), AppletError> {
+    // OK because argument is required
+    let first = args.value_of_os("mode").unwrap();
+    let mut files: Vec<&OsStr> = args.values_of_os("files").map(Iterator::collect).unwrap_or_default();
+    // With --reference there is no MODE, the first positional is already a file
+    let mode = match args.value_of_os("reference") {
+        Some(reference) => {
+            files.insert(0, first);
+            reference_mode(reference)?
+        }
+        None => parse_mode(first)?,
+    };
+    if files.is_empty() {
+        return Err(AppletError::usage(format!("missing operand after '{}'", escape_invalid_utf8(first))));
+    }
+
+    let mut run = ChmodRun {
+        mode,
+        recursive: args.is_present("recursive"),
+        verbosity: match (args.is_present("verbose"), args.is_present("changes")) {
+            (true, _) => Verbosity::All,
+            (false, true) => Verbosity::Changes,
+            (false, false) => Verbosity::Quiet,
+        },
+        umask: current_umask(),
+        check_umask: first.as_bytes()[0] == b'-' && args.value_of_os("reference").is_none(),
+        failed: false,
+        stdout,
+        stderr,
+    };
+    for file in files {
+        run.chmod(Path::new(file), true)?;
+    }
+    if run.failed {
+        return Err(AppletError::silent(EXIT_FAILURE));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chmod_main, Chmod};
+    use clap::ErrorKind;
+    use crate::core::applet::Applet;
+    use crate::core::error::{AppletError, EXIT_FAILURE, EXIT_USAGE};
+    use crate::core::test_support::setup_dir;
+    use std::ffi::{OsStr, OsString};
+    use std::fs;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use std::path::Path;
+
+    fn create_file(path: &str, mode: u32) {
+        fs::write(path, "").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    fn mode_of(path: &str) -> u32 {
+        fs::symlink_metadata(path).unwrap().permissions().mode() & 0o7777
+    }
+
+    fn run(args: &[&OsStr]) -> (Result<(), AppletError>, String, String) {
+        let matches = Chmod.get_matches(std::iter::once(OsStr::new("chmod")).chain(args.iter().copied()).map(OsStr::to_os_string).collect()).unwrap();
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let res = chmod_main(&matches, &mut stdout, &mut stderr);
+        (res, String::from_utf8(stdout).unwrap(), String::from_utf8(stderr).unwrap())
+    }
+
+    fn run_str(args: &[&str]) -> (Result<(), AppletError>, String, String) {
+        run(&args.iter().map(OsStr::new).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_chmod_modes() {
+        let dir = setup_dir("test_chmod_modes");
+        let file = format!("{}/f", dir);
+        create_file(&file, 0o644);
+        assert_eq!(run_str(&["755", &file]).0, Ok(()));
+        assert_eq!(mode_of(&file), 0o755);
+        assert_eq!(run_str(&["go-rx,u+s", &file]).0, Ok(()));
+        assert_eq!(mode_of(&file), 0o4700);
+        // A mode starting with - isn't mistaken for a flag
+        assert_eq!(run_str(&["-R", "-x", &file]).0, Ok(()));
+        assert_eq!(mode_of(&file), 0o4600);
+        assert_eq!(run_str(&["-x,+r", &file]).0, Ok(()));
+        assert_eq!(mode_of(&file), 0o4644);
+    }
+
+    #[test]
+    fn test_chmod_umask() {
+        let dir = setup_dir("test_chmod_umask");
+        let file = format!("{}/f", dir);
+        create_file(&file, 0o666);
+        let umask = unsafe { libc::umask(0o022) };
+        let (res, _, stderr) = run_str(&["-w", &file]);
+        let (explicit, _, _) = run_str(&["a-w", &file]);
+        unsafe { libc::umask(umask) };
+        assert_eq!(res, Err(AppletError::silent(EXIT_FAILURE)));
+        assert_eq!(stderr, format!("rustybox: chmod: {}: new permissions are r--rw-rw-, not r--r--r--\n", file));
+        // Saying who it is for leaves the umask out
+        assert_eq!(explicit, Ok(()));
+        assert_eq!(mode_of(&file), 0o444);
+    }
+
+    #[test]
+    fn test_chmod_unknown_options() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        for argv in [vec!["chmod", "--bogus", "f"], vec!["chmod", "-q", "f"], vec!["chmod", "755", "--bogus", "f"],
+                     vec!["chmod", "--reference=f", "-q", "f"]].iter() {
+            let err = Chmod.get_matches(args(argv)).unwrap_err();
+            assert_eq!(err.kind, ErrorKind::UnknownArgument, "{:?}", argv);
+        }
+        // After -- they are operands, a MODE that is then invalid or files
+        assert_eq!(Chmod.get_matches(args(&["chmod", "--", "-q", "f"])).unwrap().value_of("mode"), Some("-q"));
+        assert_eq!(Chmod.get_matches(args(&["chmod", "755", "--", "-q"])).unwrap().value_of("files"), Some("-q"));
+    }
+
+    #[test]
+    fn test_chmod_recursive() {
+        let dir = setup_dir("test_chmod_recursive");
+        fs::create_dir(format!("{}/sub", dir)).unwrap();
+        create_file(&format!("{}/sub/f", dir), 0o600);
+        create_file(&format!("{}/outside", dir), 0o600);
+        symlink("../outside", format!("{}/sub/link", dir)).unwrap();
+        assert_eq!(run_str(&["-R", "a+rX", &format!("{}/sub", dir)]).0, Ok(()));
+        assert_eq!(mode_of(&format!("{}/sub", dir)), 0o755);
+        assert_eq!(mode_of(&format!("{}/sub/f", dir)), 0o644);
+        // Links found while recursing are not followed
+        assert_eq!(mode_of(&format!("{}/outside", dir)), 0o600);
+    }
+
+    #[test]
+    fn test_chmod_verbose() {
+        let dir = setup_dir("test_chmod_verbose");
+        let (a, b) = (format!("{}/a", dir), format!("{}/b", dir));
+        create_file(&a, 0o644);
+        create_file(&b, 0o755);
+        let (res, stdout, _) = run_str(&["-v", "755", &a, &b]);
+        assert_eq!(res, Ok(()));
+        assert_eq!(stdout, format!("mode of '{}' changed from 0644 (rw-r--r--) to 0755 (rwxr-xr-x)\n\
+                                    mode of '{}' retained as 0755 (rwxr-xr-x)\n", a, b));
+        let (_, stdout, _) = run_str(&["-c", "u-x", &a, &b]);
+        assert_eq!(stdout, format!("mode of '{}' changed from 0755 (rwxr-xr-x) to 0655 (rw-r-xr-x)\n\
+                                    mode of '{}' changed from 0755 (rwxr-xr-x) to 0655 (rw-r-xr-x)\n", a, b));
+    }
+
+    #[test]
+    fn test_chmod_reference() {
+        let dir = setup_dir("test_chmod_reference");
+        let (reference, file) = (format!("{}/reference", dir), format!("{}/f", dir));
+        create_file(&reference, 0o1640);
+        create_file(&file, 0o777);
+        assert_eq!(run_str(&["--reference", &reference, &file]).0, Ok(()));
+        assert_eq!(mode_of(&file), 0o1640);
+    }
+
+    #[test]
+    fn test_chmod_errors() {
+        let dir = setup_dir("test_chmod_errors");
+        let file = format!("{}/f", dir);
+        create_file(&file, 0o644);
+        let missing = format!("{}/missing", dir);
+        let (res, _, stderr) = run_str(&["600", &missing, &file]);
+        assert_eq!(res, Err(AppletError::silent(EXIT_FAILURE)));
+        assert_eq!(stderr, format!("rustybox: chmod: cannot access '{}': No such file or directory\n", missing));
+        assert_eq!(mode_of(&file), 0o600);
+
+        assert_eq!(run_str(&["u+q", &file]).0, Err(AppletError::failure("invalid mode: 'u+q'".to_string())));
+        assert_eq!(run_str(&["600"]).0.unwrap_err().status(), EXIT_USAGE);
+    }
+
+    #[test]
+    fn test_chmod_non_utf8_file_name() {
+        let dir = setup_dir("test_chmod_non_utf8_file_name");
+        let path = Path::new(&dir).join(OsStr::from_bytes(b"caf\xe9"));
+        fs::write(&path, "").unwrap();
+        let (res, stdout, _) = run(&[OsStr::new("-v"), OsStr::new("0400"), path.as_os_str()]);
+        assert_eq!(res, Ok(()));
+        assert!(stdout.starts_with(&format!("mode of '{}/caf\\xe9' changed from ", dir)));
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o7777, 0o400);
+    }
+}
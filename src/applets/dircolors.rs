@@ -0,0 +1,109 @@
+use clap::{App, Arg, ArgMatches};
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use crate::core::applet::Applet;
+use crate::core::error::AppletError;
+use crate::librb::file::colordb::{compile_database, DEFAULT_DATABASE};
+use crate::librb::quote::escape_invalid_utf8;
+
+pub struct Dircolors;
+
+impl Applet for Dircolors {
+    fn name(&self) -> &'static str { "dircolors" }
+    fn usage(&self) -> &'static str { "Output commands to set the LS_COLORS environment variable" }
+
+    fn args(&self, cmd: App<'static, 'static>) -> App<'static, 'static> {
+        cmd.arg(
+            Arg::with_name("bourne-shell").short("-b").long("--sh").alias("bourne-shell").overrides_with("c-shell")
+                .help("output Bourne shell code to set LS_COLORS")
+        ).arg(
+            Arg::with_name("c-shell").short("-c").long("--csh").alias("c-shell").overrides_with("bourne-shell")
+                .help("output C shell code to set LS_COLORS")
+        ).arg(
+            Arg::with_name("print-database").short("-p").long("--print-database").help("output defaults")
+        ).arg(
+            Arg::with_name("file").index(1).help("database to read instead of the defaults")
+        )
+    }
+
+    fn main(&self, args: &ArgMatches, stdin: &mut dyn Read, stdout: &mut dyn Write, _stderr: &mut dyn Write) -> Result<(), AppletError> {
+        if args.is_present("print-database") {
+            if args.is_present("file") {
+                return Err(AppletError::usage("file operands cannot be combined with --print-database (-p)".to_string()));
+            }
+            write!(stdout, "{}", DEFAULT_DATABASE)?;
+            return Ok(());
+        }
+        let c_shell = match (args.is_present("bourne-shell"), args.is_present("c-shell")) {
+            (false, false) => guess_c_shell()?,
+            (_, c_shell) => c_shell,
+        };
+
+        let (name, database) = match args.value_of_os("file") {
+            None => ("<internal>".into(), DEFAULT_DATABASE.to_string()),
+            Some(file) => {
+                let name = escape_invalid_utf8(file);
+                let mut database = Vec::new();
+                let read = match file == "-" {
+                    true => stdin.read_to_end(&mut database),
+                    false => fs::File::open(file).and_then(|mut f| f.read_to_end(&mut database)),
+                };
+                read.map_err(|e| AppletError::io(&name, &e))?;
+                (name, String::from_utf8_lossy(&database).into_owned())
+            }
+        };
+        // Like coreutils, no TERM matches the same as the "none" terminal
+        let term = env::var("TERM").ok().filter(|term| !term.is_empty()).unwrap_or_else(|| "none".to_string());
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let ls_colors = compile_database(&database, Some((&term, &colorterm)))
+            .map_err(|e| AppletError::failure(format!("{}:{}", name, e)))?;
+
+        let ls_colors = ls_colors.replace('\'', "'\\''");
+        match c_shell {
+            true => writeln!(stdout, "setenv LS_COLORS '{}'", ls_colors)?,
+            false => writeln!(stdout, "LS_COLORS='{}';\nexport LS_COLORS", ls_colors)?,
+        }
+        Ok(())
+    }
+}
+
+/// Whether $SHELL is a C shell, when no option tells
+fn guess_c_shell() -> Result<bool, AppletError> {
+    let shell = env::var_os("SHELL").filter(|shell| !shell.is_empty())
+        .ok_or_else(|| AppletError::failure("no SHELL environment variable, and no shell type option given".to_string()))?;
+    let shell = escape_invalid_utf8(&shell);
+    let base = shell.rsplit('/').next().unwrap_or(&shell);
+    Ok(base == "csh" || base == "tcsh")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dircolors;
+    use crate::core::applet::Applet;
+    use crate::core::error::{AppletError, EXIT_USAGE};
+    use std::io;
+
+    fn dircolors(args: &[&str], stdin: &str) -> (Result<(), AppletError>, String) {
+        let matches = Dircolors.subcommand().get_matches_from(std::iter::once("dircolors").chain(args.iter().copied()));
+        let mut stdout = Vec::new();
+        let res = Dircolors.main(&matches, &mut stdin.as_bytes(), &mut stdout, &mut io::sink());
+        (res, String::from_utf8(stdout).unwrap())
+    }
+
+    #[test]
+    fn test_dircolors() {
+        let database = "DIR 01;35\n.txt 01;31\nEXEC it's\n";
+        assert_eq!(dircolors(&["-b", "-"], database).1, "LS_COLORS='di=01;35:*.txt=01;31:ex=it'\\''s:';\nexport LS_COLORS\n");
+        // The last shell option wins
+        assert_eq!(dircolors(&["-b", "-c", "-"], database).1, "setenv LS_COLORS 'di=01;35:*.txt=01;31:ex=it'\\''s:'\n");
+        let (res, stdout) = dircolors(&["-p"], "");
+        assert_eq!(res, Ok(()));
+        assert!(stdout.contains("\nDIR 01;34\n"));
+
+        assert_eq!(dircolors(&["-p", "file"], "").0.unwrap_err().status(), EXIT_USAGE);
+        assert_eq!(dircolors(&["-b", "-"], "DIR\n").0, Err(AppletError::failure("-:1: invalid line;  missing second token".to_string())));
+        assert_eq!(dircolors(&["-b", "/rustybox-not-a-file"], "").0,
+                   Err(AppletError::failure("/rustybox-not-a-file: No such file or directory".to_string())));
+    }
+}
@@ -1,18 +1,65 @@
-use clap::{App, SubCommand, ArgMatches};
+use clap::ArgMatches;
 use std::io;
+use std::io::{Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use crate::core::applet::Applet;
+use crate::core::error::AppletError;
 
-pub fn subcommand() -> App<'static, 'static>  {
-    SubCommand::with_name("env")
-        .about("Print the current environment")
+pub struct Env;
+
+impl Applet for Env {
+    fn name(&self) -> &'static str { "env" }
+    fn usage(&self) -> &'static str { "Print the current environment" }
+
+    fn main(&self, args: &ArgMatches, _stdin: &mut dyn Read, stdout: &mut dyn Write, _stderr: &mut dyn Write) -> Result<(), AppletError> {
+        Ok(_env_main(Some(args), stdout)?)
+    }
 }
 
-fn _env_main(_: Option<&ArgMatches>, writer: &mut impl std::io::Write) -> io::Result<()> {
-    for (name, value) in std::env::vars() {
-        writeln!(writer, "{name}={value}", name=name, value=value)?;
+fn _env_main(_: Option<&ArgMatches>, writer: &mut dyn Write) -> io::Result<()> {
+    // Names and values are written as they are, they need not be valid UTF-8
+    for (name, value) in std::env::vars_os() {
+        writer.write_all(name.as_bytes())?;
+        writer.write_all(b"=")?;
+        writer.write_all(value.as_bytes())?;
+        writer.write_all(b"\n")?;
     }
     Ok(())
 }
 
-pub fn env_main(matches: Option<&ArgMatches>) -> Result<(), String>{
-    _env_main(matches, &mut std::io::stdout()).or(Err("Failed to run env".to_string()))
+#[cfg(test)]
+mod tests {
+    use super::Env;
+    use crate::core::applet::Applet;
+    use crate::core::error::AppletError;
+    use crate::core::pipe::{BrokenPipeWriter, EXIT_BROKEN_PIPE};
+    use std::ffi::OsStr;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+
+    fn env_lines() -> Vec<Vec<u8>> {
+        let matches = Env.subcommand().get_matches_from(vec!["env"]);
+        let mut output: Vec<u8> = Vec::new();
+        assert!(Env.main(&matches, &mut io::empty(), &mut output, &mut io::sink()).is_ok());
+        output.split(|byte| *byte == b'\n').map(<[u8]>::to_vec).collect()
+    }
+
+    #[test]
+    fn test_env() {
+        std::env::set_var("RUSTYBOX_ENV_TEST", "value");
+        assert!(env_lines().iter().any(|line| line == b"RUSTYBOX_ENV_TEST=value"));
+    }
+
+    #[test]
+    fn test_env_non_utf8() {
+        std::env::set_var(OsStr::from_bytes(b"RUSTYBOX_ENV_TEST_\xfe"), OsStr::from_bytes(b"caf\xe9"));
+        assert!(env_lines().iter().any(|line| line == b"RUSTYBOX_ENV_TEST_\xfe=caf\xe9"));
+    }
+
+    #[test]
+    fn test_env_broken_pipe() {
+        let matches = Env.subcommand().get_matches_from(vec!["env"]);
+        let err = Env.main(&matches, &mut io::empty(), &mut BrokenPipeWriter, &mut io::sink()).unwrap_err();
+        assert_eq!(err, AppletError::silent(EXIT_BROKEN_PIPE));
+    }
 }
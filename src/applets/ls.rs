@@ -1,126 +1,886 @@
-use clap::{App, Arg, ArgMatches, Values, SubCommand};
+use clap::{App, Arg, ArgMatches, OsValues};
 use core::option::Option::{None, Some};
 use core::option::Option;
 use core::result::Result;
 use core::result::Result::Ok;
+use std::borrow::Cow;
 use std::io;
-use std::path::PathBuf;
-use crate::librb::file::filemeta::{FileMetadata};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use crate::librb::file::colors::LsColors;
+use crate::librb::file::filemeta::{FileMetadata, LongFormat, LongOptions, TimeField};
+use crate::librb::file::filetype::FileType;
+use crate::librb::file::permissions::PermissionsMask;
+use crate::librb::file::sort::{SortKey, SortOrder};
+use std::convert::TryFrom;
+use std::ffi::OsStr;
 use std::str::FromStr;
 use strum_macros::EnumString;
+use crate::core::applet::Applet;
+use crate::core::error::{AppletError, EXIT_TROUBLE, report, strerror};
+use crate::librb::quote::escape_invalid_utf8;
+use crate::librb::quoting::{Quoted, Quoting, QuotingStyle};
+use crate::librb::size::SizeFormat;
+use crate::librb::terminal::{columns_from_env, Terminal};
+use crate::librb::time::TimeStyle;
 
-pub fn subcommand() -> App<'static, 'static>  {
-    SubCommand::with_name("ls")
-        .about("List files")
-        .arg(
-            Arg::with_name("all").short("-a").long("-all").takes_value(false).help("show hidden and 'dot' files")
+pub struct Ls;
+
+fn is_number(value: String) -> Result<(), String> {
+    value.parse::<usize>().map(|_| ()).map_err(|_| format!("invalid line width: '{}'", value))
+}
+
+impl Applet for Ls {
+    fn name(&self) -> &'static str { "ls" }
+    fn usage(&self) -> &'static str { "List files" }
+
+    fn missing_values(&self) -> &'static [(&'static str, &'static str)] {
+        &[("--classify", "always")]
+    }
+
+    fn args(&self, cmd: App<'static, 'static>) -> App<'static, 'static> {
+        cmd.arg(
+            Arg::with_name("all").short("-a").long("--all").multiple(true).help("do not ignore entries starting with .")
+        ).arg(
+            Arg::with_name("almost-all").short("-A").long("--almost-all").multiple(true).help("do not list implied . and ..")
+        ).arg(
+            Arg::with_name("directory").short("-d").long("--directory").help("list directories themselves, not their contents")
+        ).arg(
+            Arg::with_name("color").long("--color").value_name("WHEN").takes_value(true).min_values(0).require_equals(true)
+                .possible_values(&["never", "no", "none", "auto", "tty", "if-tty", "always", "yes", "force"])
+                .help("color the output WHEN, always without it, see LS_COLORS and dircolors")
+        ).arg(
+            Arg::with_name("long-display").short("-l").multiple(true).help("use long listing format")
+        ).arg(
+            Arg::with_name("long-no-owner").short("-g").multiple(true).help("like -l, but do not list owner")
+        ).arg(
+            Arg::with_name("long-no-group").short("-o").multiple(true).help("like -l, but do not list group information")
+        ).arg(
+            Arg::with_name("numeric-uid-gid").short("-n").long("--numeric-uid-gid").multiple(true)
+                .help("like -l, but list numeric user and group IDs")
+        ).arg(
+            Arg::with_name("author").long("--author").help("with -l, print the author of each file")
+        ).arg(
+            Arg::with_name("inode").short("-i").long("--inode").help("print the index number of each file")
+        ).arg(
+            Arg::with_name("one-per-line").short("-1").multiple(true).help("list one file per line")
+        ).arg(
+            Arg::with_name("columns").short("-C").multiple(true).help("list entries by columns")
+        ).arg(
+            Arg::with_name("across").short("-x").multiple(true).help("list entries by lines instead of by columns")
+        ).arg(
+            Arg::with_name("commas").short("-m").multiple(true).help("fill width with a comma separated list of entries")
+        ).arg(
+            Arg::with_name("format").long("--format").value_name("WORD").takes_value(true).multiple(true).number_of_values(1)
+                .possible_values(&["across", "horizontal", "commas", "long", "verbose", "single-column", "vertical", "json"])
+                .help("across -x, commas -m, horizontal -x, long -l, single-column -1, verbose -l, vertical -C, json --json")
+        ).arg(
+            Arg::with_name("json").long("--json").multiple(true)
+                .help("print a JSON object per entry, one per line, see JSON output in README.md")
+        ).arg(
+            Arg::with_name("width").short("-w").long("--width").value_name("COLS").takes_value(true).validator(is_number)
+                .help("set output width to COLS, 0 means no limit")
+        ).arg(
+            Arg::with_name("tabsize").short("-T").long("--tabsize").value_name("COLS").takes_value(true).validator(is_number)
+                .help("assume tab stops at each COLS instead of 8")
+        ).arg(
+            Arg::with_name("size").short("-s").long("--size").help("print the allocated size of each file, in blocks")
+        ).arg(
+            Arg::with_name("human-readable").short("-h").long("--human-readable").multiple(true)
+                .help("with -l and -s, print sizes like 1K 234M 2G etc.")
+        ).arg(
+            Arg::with_name("si").long("--si").multiple(true).help("likewise, but use powers of 1000 not 1024")
+        ).arg(
+            Arg::with_name("kibibytes").short("-k").long("--kibibytes").multiple(true)
+                .help("default to 1024-byte blocks for file system usage; used only with -s and per directory totals")
+        ).arg(
+            Arg::with_name("block-size").long("--block-size").value_name("SIZE").takes_value(true).multiple(true)
+                .number_of_values(1).validator(|spec| SizeFormat::parse(&spec).map(|_| ()))
+                .help("with -l, scale sizes by SIZE when printing them; e.g., '--block-size=M'")
+        ).arg(
+            Arg::with_name("classify").short("-F").multiple(true).help("append indicator (one of */=@|) to entries")
+        ).arg(
+            Arg::with_name("classify-when").long("--classify").value_name("WHEN").takes_value(true)
+                .require_equals(true).multiple(true).possible_values(&["never", "no", "none", "auto", "tty", "if-tty", "always", "yes", "force"])
+                .help("like -F but only WHEN, always without it")
+        ).arg(
+            Arg::with_name("file-type").long("--file-type").multiple(true).help("likewise, except do not append '*'")
         ).arg(
-            Arg::with_name("color").short("-c").long("-color").takes_value(true).possible_values(&["never", "auto", "always"]).help("Color the output")
+            Arg::with_name("slash").short("-p").multiple(true).help("append / indicator to directories")
         ).arg(
-            Arg::with_name("long-display").short("-l").takes_value(false).help("use long listing format")
+            Arg::with_name("indicator-style").long("--indicator-style").value_name("WORD").takes_value(true).multiple(true)
+                .number_of_values(1).possible_values(&["none", "slash", "file-type", "classify"])
+                .help("append indicator with style WORD to entry names")
+        ).arg(
+            Arg::with_name("literal").short("-N").long("--literal").multiple(true).help("print entry names without quoting")
+        ).arg(
+            Arg::with_name("escape").short("-b").long("--escape").multiple(true).help("print C-style escapes for nongraphic characters")
+        ).arg(
+            Arg::with_name("quote-name").short("-Q").long("--quote-name").multiple(true).help("enclose entry names in double quotes")
+        ).arg(
+            Arg::with_name("quoting-style").long("--quoting-style").value_name("WORD").takes_value(true).multiple(true)
+                .number_of_values(1).possible_values(QuotingStyle::NAMES)
+                .help("use quoting style WORD for entry names, the default comes from QUOTING_STYLE")
+        ).arg(
+            Arg::with_name("hide-control-chars").short("-q").long("--hide-control-chars").multiple(true)
+                .help("print ? instead of nongraphic characters")
+        ).arg(
+            Arg::with_name("show-control-chars").long("--show-control-chars").multiple(true)
+                .help("show nongraphic characters as-is (the default, unless program is 'ls' and output is a terminal)")
+        ).arg(
+            Arg::with_name("recursive").short("-R").long("--recursive").help("list subdirectories recursively")
+        ).arg(
+            Arg::with_name("dereference").short("-L").long("--dereference").help("show and descend into what symbolic links point to")
+        ).arg(
+            Arg::with_name("access-time").short("-u").multiple(true)
+                .help("with -lt: sort by, and show, access time; with -l: show access time and sort by name; otherwise: sort by access time")
+        ).arg(
+            Arg::with_name("change-time").short("-c").multiple(true)
+                .help("with -lt: sort by, and show, ctime; with -l: show ctime and sort by name; otherwise: sort by ctime")
+        ).arg(
+            Arg::with_name("time").long("--time").value_name("WORD").takes_value(true).multiple(true).number_of_values(1)
+                .possible_values(&["atime", "access", "use", "ctime", "status", "mtime", "modification", "birth", "creation"])
+                .help("show and sort by the time WORD instead of the modification time, like -u and -c")
+        ).arg(
+            Arg::with_name("time-style").long("--time-style").value_name("TIME_STYLE").takes_value(true).multiple(true)
+                .number_of_values(1).help("with -l, show times using full-iso, long-iso, iso, locale or +FORMAT, the default comes from TIME_STYLE")
+        ).arg(
+            Arg::with_name("full-time").long("--full-time").multiple(true).help("like -l --time-style=full-iso")
+        ).arg(
+            Arg::with_name("sort-time").short("-t").multiple(true).help("sort by modification time, newest first")
+        ).arg(
+            Arg::with_name("sort-size").short("-S").multiple(true).help("sort by file size, largest first")
+        ).arg(
+            Arg::with_name("sort-extension").short("-X").multiple(true).help("sort alphabetically by extension")
+        ).arg(
+            Arg::with_name("sort-version").short("-v").multiple(true).help("natural sort of (version) numbers within text")
+        ).arg(
+            Arg::with_name("sort-none").short("-U").multiple(true).help("do not sort, list entries in directory order")
+        ).arg(
+            Arg::with_name("sort").long("--sort").value_name("WORD").takes_value(true).multiple(true).number_of_values(1)
+                .possible_values(&["none", "size", "time", "version", "extension"]).help("sort by WORD instead of name")
+        ).arg(
+            Arg::with_name("reverse").short("-r").long("--reverse").help("reverse order while sorting")
+        ).arg(
+            Arg::with_name("group-directories-first").long("--group-directories-first").help("group directories before files")
         ).arg(
             Arg::with_name("directories").help("Files/Directories to list").multiple(true).index(1)
-    )
+        )
+    }
+
+    fn main(&self, args: &ArgMatches, _stdin: &mut dyn Read, stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), AppletError> {
+        _ls_main(args, &Terminal::stdout(), stdout, stderr)
+    }
 }
 
-fn display_entry(meta: FileMetadata, fmt: &DisplayFormat, writer: &mut impl std::io::Write) -> std::io::Result<()> {
-    if fmt.long_display {
-        return writeln!(*writer, "{}", meta);
+/// In 512 byte units, like the allocated size of each file
+fn total_blocks(entries: &[FileMetadata]) -> u64 {
+    entries.iter().map(FileMetadata::blocks).sum()
+}
+
+/// Columns are never narrower than this, separator included
+const MIN_COLUMN_WIDTH: usize = 3;
+
+/// Pad from column `from` to column `to`, with tabs where they fit like coreutils
+fn indent(writer: &mut dyn Write, mut from: usize, to: usize, tabsize: usize) -> io::Result<()> {
+    while from < to {
+        if tabsize != 0 && to / tabsize > (from + 1) / tabsize {
+            write!(writer, "\t")?;
+            from += tabsize - from % tabsize;
+        } else {
+            write!(writer, " ")?;
+            from += 1;
+        }
     }
+    Ok(())
+}
 
-    write!(*writer, "{}", meta.short_name())
+/// The widths (separator included) of the most columns the names fit in, filled down the columns or
+/// across the rows
+fn column_widths(lengths: &[usize], line_width: usize, across: bool) -> Vec<usize> {
+    let max_columns = (line_width / MIN_COLUMN_WIDTH).clamp(1, lengths.len().max(1));
+    for columns in (1..=max_columns).rev() {
+        let rows = lengths.len().div_ceil(columns);
+        let mut widths = vec![MIN_COLUMN_WIDTH; columns];
+        for (i, length) in lengths.iter().enumerate() {
+            let column = if across { i % columns } else { i / rows };
+            let separator = if column == columns - 1 { 0 } else { 2 };
+            widths[column] = widths[column].max(length + separator);
+        }
+        if columns == 1 || widths.iter().sum::<usize>() < line_width {
+            return widths;
+        }
+    }
+    vec![MIN_COLUMN_WIDTH]
+}
+
+/// A name as it is printed, with how many columns it takes on the terminal
+struct Label {
+    text: Vec<u8>,
+    width: usize,
 }
 
-fn list_dirs(path: &PathBuf, fmt: &DisplayFormat, writer: &mut impl std::io::Write) -> Result<(), io::Error>{
-    /*if fmt.show_hidden {
-        let meta = FileMetadata::for_path(&path.join(".")).unwrap();
-        if fmt.should_diplay(&meta) {
-            display_entry(meta, fmt, writer)?;
+impl Label {
+    /// The quoted name of `meta`, painted, with its indicator. `pad` lines it up with quoted names by a space
+    /// where their opening quote is.
+    fn for_entry(meta: &FileMetadata, name: Quoted, pad: bool, indicator: Option<char>, fmt: &DisplayFormat) -> Label {
+        let pad = if pad && !name.quoted { " " } else { "" };
+        let width = pad.len() + name.width + indicator.map_or(0, |_| 1);
+        let mut text = pad.as_bytes().to_vec();
+        match &fmt.colors {
+            Some(colors) => text.extend_from_slice(&colors.paint(meta, false, Cow::Owned(name.text))),
+            None => text.extend_from_slice(&name.text),
         }
-        let meta = FileMetadata::for_path(&path.join("..")).unwrap();
-        if fmt.should_diplay(&meta) {
-            display_entry(meta, fmt, writer)?;
+        text.extend(indicator.map(|indicator| indicator as u8));
+        Label { text, width }
+    }
+
+    /// Put columns like the inode and the allocated size in front of the name
+    fn prefixed(self, prefix: &str) -> Label {
+        Label { text: [prefix.as_bytes(), &self.text].concat(), width: prefix.chars().count() + self.width }
+    }
+}
+
+fn write_columns(labels: &[Label], fmt: &DisplayFormat, writer: &mut dyn Write) -> io::Result<()> {
+    let lengths: Vec<usize> = labels.iter().map(|label| label.width).collect();
+    let across = fmt.layout == Layout::Across;
+    let widths = column_widths(&lengths, fmt.width, across);
+    let rows = labels.len().div_ceil(widths.len());
+    for row in 0..rows {
+        let mut pos = 0;
+        let cells = match across {
+            true => (row * widths.len()..labels.len().min((row + 1) * widths.len())).collect::<Vec<_>>(),
+            false => (row..labels.len()).step_by(rows).collect(),
+        };
+        for (column, i) in cells.iter().enumerate() {
+            if column > 0 {
+                let previous = cells[column - 1];
+                indent(writer, pos + lengths[previous], pos + widths[column - 1], fmt.tabsize)?;
+                pos += widths[column - 1];
+            }
+            writer.write_all(&labels[*i].text)?;
         }
-    }*/
-    let mut is_first: bool = true;
-    for entry in std::fs::read_dir(path)? {
-        if let Ok(entry) = entry {
-            if let Some(meta) = FileMetadata::for_path(&entry.path()) {
-                if fmt.should_diplay(&meta) {
-                    if !is_first {
-                        write!(*writer, " ").unwrap();
-                    }
-                    is_first = false;
-                    display_entry(meta, fmt, writer)?;
-                }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn write_commas(labels: &[Label], fmt: &DisplayFormat, writer: &mut dyn Write) -> io::Result<()> {
+    let mut pos = 0;
+    for (i, label) in labels.iter().enumerate() {
+        if i > 0 {
+            if pos + label.width + 2 < fmt.width {
+                write!(writer, ", ")?;
+                pos += 2;
+            } else {
+                writeln!(writer, ",")?;
+                pos = 0;
             }
         }
+        writer.write_all(&label.text)?;
+        pos += label.width;
+    }
+    if !labels.is_empty() {
+        writeln!(writer)?;
     }
     Ok(())
 }
 
-fn print_dirs(dirs: Option<Values>, fmt: &DisplayFormat, writer: &mut impl std::io::Write) -> Result<(), std::io::Error> {
-    match dirs {
-        Some(dirs) => {
-            for dir in dirs {
-                let path = PathBuf::from(dir);
-                list_dirs(&path, &fmt, writer)?;
+/// `entries` with the columns of the long format, -i and -s as wide as `sized_for` needs them
+fn display_entries(entries: &[FileMetadata], sized_for: &[FileMetadata], fmt: &DisplayFormat, writer: &mut dyn Write) -> io::Result<()> {
+    // The columns in front of the names, right aligned unless the names are just separated by commas
+    let column = |shown: bool, value: &dyn Fn(&FileMetadata) -> String| -> Vec<String> {
+        if !shown {
+            return vec![String::new(); entries.len()];
+        }
+        let width = match fmt.layout {
+            Layout::Commas => 0,
+            _ => sized_for.iter().map(|meta| value(meta).len()).max().unwrap_or(0),
+        };
+        entries.iter().map(|meta| format!("{:>width$} ", value(meta), width=width)).collect()
+    };
+    let inodes = column(fmt.show_inodes, &|meta| meta.ino().to_string());
+    let blocks = column(fmt.show_blocks, &|meta| fmt.block_sizes.format(meta.blocks(), 512));
+    let prefixes = inodes.into_iter().zip(blocks).map(|(inode, blocks)| inode + &blocks);
+
+    let names: Vec<Quoted> = entries.iter().map(|meta| fmt.names.quote(&meta.name)).collect();
+    // Like coreutils, once a name in the listing is quoted the others move over to line up with it
+    let pad = fmt.align_quotes && names.iter().any(|name| name.quoted);
+
+    if fmt.layout == Layout::Long {
+        let long = LongFormat::with_options(sized_for, &fmt.long);
+        for ((meta, name), prefix) in entries.iter().zip(names).zip(prefixes) {
+            write!(writer, "{}{}", prefix, long.display_columns(meta))?;
+            // The indicator of a link goes after its target, for what the target is
+            let indicator = match meta.link_target() {
+                Some(_) => None,
+                None => fmt.indicators.for_mode(meta.mode()),
+            };
+            writer.write_all(&Label::for_entry(meta, name, pad, indicator, fmt).text)?;
+            if let Some(target) = meta.link_target() {
+                let target = fmt.names.quote(target.as_os_str()).text;
+                writer.write_all(b" -> ")?;
+                match &fmt.colors {
+                    Some(colors) => writer.write_all(&colors.paint(meta, true, Cow::Owned(target)))?,
+                    None => writer.write_all(&target)?,
+                }
+                // Like coreutils, -p leaves targets alone
+                let indicator = match fmt.indicators {
+                    IndicatorStyle::Slash => None,
+                    indicators => meta.link_mode().and_then(|mode| indicators.for_mode(mode)),
+                };
+                if let Some(indicator) = indicator {
+                    write!(writer, "{}", indicator)?;
+                }
             }
+            writeln!(writer)?;
         }
-        None => {
-            list_dirs(&std::env::current_dir()?, &fmt, writer)?;
+        return Ok(());
+    }
+
+    let labels: Vec<Label> = entries.iter().zip(names).zip(prefixes)
+        .map(|((meta, name), prefix)| Label::for_entry(meta, name, pad, fmt.indicators.for_mode(meta.mode()), fmt).prefixed(&prefix))
+        .collect();
+    match fmt.layout {
+        Layout::Columns | Layout::Across => write_columns(&labels, fmt, writer),
+        Layout::Commas => write_commas(&labels, fmt, writer),
+        Layout::Long | Layout::OnePerLine | Layout::Json => {
+            for label in labels {
+                writer.write_all(&label.text)?;
+                writeln!(writer)?;
+            }
+            Ok(())
         }
     }
+}
+
+/// The JSON records of `entries`, found in `dir` or given as operands
+fn write_json(entries: &[FileMetadata], dir: Option<&Path>, writer: &mut dyn Write) -> io::Result<()> {
+    for meta in entries {
+        let path = dir.map_or_else(|| PathBuf::from(&meta.name), |dir| dir.join(&meta.name));
+        writeln!(writer, "{}", meta.to_json(&path))?;
+    }
     Ok(())
 }
 
-fn _ls_main(matches: Option<&ArgMatches>, writer: &mut impl std::io::Write) -> Result<(), String> {
-    let matches = matches.ok_or("wtf")?;
-    let fmt = build_display_fmt(matches);
-    let dirs = matches.values_of("directories");
-    print_dirs(dirs, &fmt, writer).or(Err("print failed".to_string()))
+/// One ls run, which can span many directories with -R
+struct Listing<'a> {
+    fmt: &'a DisplayFormat,
+    writer: &'a mut dyn Write,
+    stderr: &'a mut dyn Write,
+    /// (device, inode) of the directories being listed, a subdirectory among them is a loop
+    ancestors: Vec<(u64, u64)>,
+    sections: usize,
+    /// Whether there are several operands, each directory gets a header then
+    headers: bool,
+    /// The exit status so far
+    status: i32,
+}
+
+impl Listing<'_> {
+    fn fail(&mut self, err: AppletError) {
+        report(self.stderr, Ls.name(), &err);
+        self.status = self.status.max(err.status());
+    }
+
+    /// Entries that can't be read or stat'ed are reported and left out, the others are still listed
+    fn read_entries(&mut self, path: &Path) -> io::Result<Vec<FileMetadata>> {
+        let mut entries = Vec::new();
+        let read_dir = std::fs::read_dir(path)?;
+        if self.fmt.show_dot_entries {
+            // read_dir leaves them out
+            for name in [".", ".."].iter() {
+                if let Ok(mut meta) = self.fmt.load(&path.join(name), self.fmt.dereference) {
+                    meta.name = name.into();
+                    entries.push(meta);
+                }
+            }
+        }
+        for entry in read_dir {
+            let entry_path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    self.fail(AppletError::io(escape_invalid_utf8(path.as_os_str()), &e));
+                    continue;
+                }
+            };
+            // A dangling link can still be shown as the link itself
+            let meta = match self.fmt.dereference {
+                true => self.fmt.load(&entry_path, true).or_else(|_| self.fmt.load(&entry_path, false)),
+                false => self.fmt.load(&entry_path, false),
+            };
+            match meta {
+                Ok(meta) if self.fmt.should_diplay(&meta) => entries.push(meta),
+                Ok(_) => (),
+                Err(e) => self.fail(AppletError::io(escape_invalid_utf8(entry_path.as_os_str()), &e)),
+            }
+        }
+        self.fmt.sort.sort(&mut entries);
+        Ok(entries)
+    }
+
+    /// Unreadable directories are reported and skipped, like coreutils an operand among them is serious trouble
+    fn list_dir(&mut self, path: &Path) -> Result<(), AppletError> {
+        // Operands are the only directory on the ancestors stack
+        let is_operand = self.ancestors.len() <= 1;
+        let entries = match self.read_entries(path) {
+            Ok(entries) => entries,
+            Err(e) if is_operand => {
+                self.fail(AppletError::new(EXIT_TROUBLE, format!("{}: {}", escape_invalid_utf8(path.as_os_str()), strerror(&e))));
+                return Ok(());
+            }
+            Err(e) => {
+                self.fail(AppletError::io(escape_invalid_utf8(path.as_os_str()), &e));
+                return Ok(());
+            }
+        };
+        if self.fmt.layout == Layout::Json {
+            // The records have their paths, they need no headers
+            write_json(&entries, Some(path), self.writer)?;
+        } else {
+            if self.fmt.recursive || self.headers {
+                if self.sections > 0 {
+                    writeln!(self.writer)?;
+                }
+                self.writer.write_all(&self.fmt.dirnames.quote(path.as_os_str()).text)?;
+                writeln!(self.writer, ":")?;
+            }
+            self.sections += 1;
+            if self.fmt.layout == Layout::Long || self.fmt.show_blocks {
+                writeln!(self.writer, "total {}", self.fmt.block_sizes.format(total_blocks(&entries), 512))?;
+            }
+            display_entries(&entries, &entries, self.fmt, self.writer)?;
+        }
+        if !self.fmt.recursive {
+            return Ok(());
+        }
+
+        for meta in entries.iter().filter(|meta| meta.is_dir() && meta.name != "." && meta.name != "..") {
+            let subdir = path.join(&meta.name);
+            let id = (meta.dev(), meta.ino());
+            if self.ancestors.contains(&id) {
+                self.fail(AppletError::new(EXIT_TROUBLE, format!("{}: not listing already-listed directory", escape_invalid_utf8(subdir.as_os_str()))));
+                continue;
+            }
+            self.ancestors.push(id);
+            self.list_dir(&subdir)?;
+            self.ancestors.pop();
+        }
+        Ok(())
+    }
+
+    /// An operand as an entry named as it was given. Like coreutils, a link to a directory is followed unless the
+    /// link itself is of interest: with -d, -l or -F.
+    fn load_operand(&mut self, operand: &OsStr) -> Option<FileMetadata> {
+        let path = Path::new(operand);
+        let meta = match self.fmt.dereference {
+            true => self.fmt.load(path, true),
+            false if self.fmt.follow_operand_links => {
+                FileMetadata::load(path, true).ok().filter(FileMetadata::is_dir).map_or_else(|| self.fmt.load(path, false), Ok)
+            }
+            false => self.fmt.load(path, false),
+        };
+        match meta {
+            Ok(mut meta) => {
+                meta.name = operand.to_os_string();
+                Some(meta)
+            }
+            Err(e) => {
+                self.fail(AppletError::new(EXIT_TROUBLE, format!("{}: {}", escape_invalid_utf8(operand), strerror(&e))));
+                None
+            }
+        }
+    }
+
+    /// Files first, all in one listing, then the directories one by one
+    fn list_operands(&mut self, operands: &[&OsStr]) -> Result<(), AppletError> {
+        let fmt = self.fmt;
+        self.headers = operands.len() > 1;
+        let mut metas: Vec<FileMetadata> = operands.iter().filter_map(|operand| self.load_operand(operand)).collect();
+        fmt.sort.sort(&mut metas);
+        // Stable, so both the files and the directories stay sorted
+        let is_listed_dir = |meta: &FileMetadata| meta.is_dir() && !fmt.directories_as_files;
+        metas.sort_by_key(is_listed_dir);
+        let (files, dirs) = metas.split_at(metas.iter().position(is_listed_dir).unwrap_or(metas.len()));
+        if fmt.layout == Layout::Json {
+            write_json(files, None, self.writer)?;
+        } else if !files.is_empty() {
+            // Like coreutils, the columns also fit the directories, they were all looked at together
+            display_entries(files, &metas, fmt, self.writer)?;
+            self.sections += 1;
+        }
+        for dir in dirs {
+            // The directory a link operand points to counts for loops
+            self.ancestors = vec![(dir.dev(), dir.ino())];
+            self.list_dir(Path::new(&dir.name))?;
+        }
+        Ok(())
+    }
+}
+
+fn _ls_main(matches: &ArgMatches, terminal: &Terminal, writer: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), AppletError> {
+    let colors = load_colors(matches, terminal, stderr);
+    // Like coreutils, the time style only has to make sense when it is used
+    let times = match layout(matches, terminal) {
+        Layout::Long => time_style(matches).map_err(|e| AppletError::new(EXIT_TROUBLE, e))?,
+        _ => TimeStyle::LOCALE,
+    };
+    let fmt = build_display_fmt(matches, terminal, colors, times);
+    list(&fmt, matches.values_of_os("directories"), writer, stderr)
 }
 
-pub fn ls_main(matches: Option<&ArgMatches>) -> Result<(), String> {
-    _ls_main(matches, &mut std::io::stdout())
+fn list(fmt: &DisplayFormat, operands: Option<OsValues>, writer: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), AppletError> {
+    let mut listing = Listing { fmt, writer, stderr, ancestors: Vec::new(), sections: 0, headers: false, status: 0 };
+    let operands: Vec<&OsStr> = match operands {
+        Some(operands) => operands.collect(),
+        None => vec![OsStr::new(".")],
+    };
+    listing.list_operands(&operands)?;
+    if let Some(colors) = &fmt.colors {
+        listing.writer.write_all(&colors.finish())?;
+    }
+    match listing.status {
+        0 => Ok(()),
+        status => Err(AppletError::silent(status)),
+    }
 }
 
 
-#[derive(EnumString, PartialEq)]
+#[derive(EnumString, PartialEq, Debug)]
 enum ColorOption {
-    #[strum(serialize = "never")]
+    #[strum(serialize = "never", serialize = "no", serialize = "none")]
     Never,
-    #[strum(serialize = "auto")]
+    #[strum(serialize = "auto", serialize = "tty", serialize = "if-tty")]
     Auto,
-    #[strum(serialize = "always")]
+    #[strum(serialize = "always", serialize = "yes", serialize = "force")]
     Always
 }
 
+#[derive(EnumString, PartialEq, Clone, Copy, Debug)]
+enum IndicatorStyle {
+    #[strum(serialize = "none")]
+    None,
+    /// Only directories
+    #[strum(serialize = "slash")]
+    Slash,
+    /// All but executables
+    #[strum(serialize = "file-type")]
+    FileType,
+    #[strum(serialize = "classify")]
+    Classify,
+}
+
+impl IndicatorStyle {
+    /// The indicator of a file with `mode`, if this style shows one for it
+    fn for_mode(self, mode: u32) -> Option<char> {
+        let file_type = FileType::try_from(mode & 0o170000).ok()?;
+        match self {
+            IndicatorStyle::None => None,
+            IndicatorStyle::Slash => Some('/').filter(|_| file_type == FileType::Directory),
+            IndicatorStyle::FileType => file_type.indicator(false),
+            IndicatorStyle::Classify => file_type.indicator(PermissionsMask::build(mode).is_executable()),
+        }
+    }
+}
+
+#[derive(EnumString, PartialEq, Clone, Copy, Debug)]
+enum Layout {
+    #[strum(serialize = "long", serialize = "verbose")]
+    Long,
+    #[strum(serialize = "single-column")]
+    OnePerLine,
+    /// Filled down the columns
+    #[strum(serialize = "vertical")]
+    Columns,
+    /// Filled across the rows
+    #[strum(serialize = "across", serialize = "horizontal")]
+    Across,
+    #[strum(serialize = "commas")]
+    Commas,
+    /// A JSON object per line, for programs
+    #[strum(serialize = "json")]
+    Json,
+}
+
 struct DisplayFormat {
     show_hidden: bool,
-    long_display: bool,
-    color: ColorOption
+    /// -a lists . and .. too
+    show_dot_entries: bool,
+    /// -d, directory operands are listed like files
+    directories_as_files: bool,
+    /// Operands that are links to directories are listed as the directories
+    follow_operand_links: bool,
+    layout: Layout,
+    /// Line width for the layouts, 0 (no limit) is usize::MAX
+    width: usize,
+    tabsize: usize,
+    recursive: bool,
+    dereference: bool,
+    sort: SortOrder,
+    colors: Option<LsColors>,
+    /// How file sizes and times are shown in the long layout
+    long: LongOptions,
+    /// How allocated sizes are shown, in the -s column and the total
+    block_sizes: SizeFormat,
+    show_blocks: bool,
+    /// The inode column of -i, before the -s one
+    show_inodes: bool,
+    indicators: IndicatorStyle,
+    names: Quoting,
+    /// Of the -R headers, which also quote the : after them
+    dirnames: Quoting,
+    /// Pad unquoted names by a space when others are quoted
+    align_quotes: bool,
+    /// What links point to is shown, by colors or by indicators
+    link_modes: bool,
 }
 
 impl DisplayFormat {
     fn should_diplay(&self, f: &FileMetadata) -> bool {
         !f.is_hidden() || self.show_hidden
     }
+
+    /// FileMetadata::load, which only looks at what links point to when the listing shows it
+    fn load(&self, path: &Path, dereference: bool) -> io::Result<FileMetadata> {
+        let meta = FileMetadata::load(path, dereference)?;
+        Ok(if self.link_modes { meta.with_link_mode(path) } else { meta })
+    }
+}
+
+/// The value of the flag given last, options like -t and -S override each other like in coreutils
+fn last_flag<T: Copy>(matches: &ArgMatches, flags: &[(&str, T)]) -> Vec<(usize, T)> {
+    flags.iter().filter_map(|(flag, value)| Some((matches.indices_of(flag)?.max()?, *value))).collect()
+}
+
+/// The last sort option, if any
+fn sort_key(matches: &ArgMatches) -> Option<SortKey> {
+    let flags = [("sort-time", SortKey::Time), ("sort-size", SortKey::Size), ("sort-extension", SortKey::Extension),
+                 ("sort-version", SortKey::Version), ("sort-none", SortKey::None)];
+    let mut chosen = last_flag(matches, &flags);
+    if let (Some(indices), Some(words)) = (matches.indices_of("sort"), matches.values_of("sort")) {
+        // possible_values only lets known words through
+        chosen.extend(indices.zip(words).map(|(index, word)| (index, SortKey::from_str(word).unwrap())));
+    }
+    chosen.into_iter().max_by_key(|(index, _)| *index).map(|(_, key)| key)
+}
+
+/// The last of -u, -c and --time
+fn time_field(matches: &ArgMatches) -> TimeField {
+    let mut chosen = last_flag(matches, &[("access-time", TimeField::Access), ("change-time", TimeField::Change)]);
+    if let (Some(indices), Some(words)) = (matches.indices_of("time"), matches.values_of("time")) {
+        // possible_values only lets known words through
+        chosen.extend(indices.zip(words).map(|(index, word)| (index, TimeField::from_str(word).unwrap())));
+    }
+    chosen.into_iter().max_by_key(|(index, _)| *index).map_or(TimeField::Modification, |(_, field)| field)
+}
+
+/// The last of --time-style and --full-time, then TIME_STYLE, then the locale's
+fn time_style(matches: &ArgMatches) -> Result<TimeStyle, String> {
+    let mut chosen: Vec<(usize, String)> = matches.indices_of("full-time").into_iter().flatten()
+        .map(|index| (index, "full-iso".to_string())).collect();
+    if let (Some(indices), Some(specs)) = (matches.indices_of("time-style"), matches.values_of("time-style")) {
+        chosen.extend(indices.zip(specs.map(str::to_string)));
+    }
+    let spec = chosen.into_iter().max_by_key(|(index, _)| *index).map(|(_, spec)| spec)
+        .or_else(|| std::env::var("TIME_STYLE").ok());
+    spec.map_or(Ok(TimeStyle::LOCALE), |spec| TimeStyle::parse(&spec))
+}
+
+/// Columns on a terminal, one name per line otherwise
+fn layout(matches: &ArgMatches, terminal: &Terminal) -> Layout {
+    let flags = [("long-display", Layout::Long), ("full-time", Layout::Long), ("long-no-owner", Layout::Long),
+                 ("long-no-group", Layout::Long), ("numeric-uid-gid", Layout::Long), ("columns", Layout::Columns),
+                 ("across", Layout::Across), ("commas", Layout::Commas), ("json", Layout::Json)];
+    let mut chosen = last_flag(matches, &flags);
+    if let (Some(indices), Some(words)) = (matches.indices_of("format"), matches.values_of("format")) {
+        // possible_values only lets known words through
+        chosen.extend(indices.zip(words).map(|(index, word)| (index, Layout::from_str(word).unwrap())));
+    }
+    let default = if terminal.is_tty { Layout::Columns } else { Layout::OnePerLine };
+    let (index, layout) = chosen.into_iter().max_by_key(|(index, _)| *index).unwrap_or((0, default));
+    // Like coreutils, -1 has no effect after -l and the options like it
+    match last_flag(matches, &[("one-per-line", Layout::OnePerLine)]).pop() {
+        Some((one_per_line, _)) if one_per_line > index && layout != Layout::Long => Layout::OnePerLine,
+        _ => layout,
+    }
+}
+
+/// -w, then COLUMNS, then the terminal's own width
+fn line_width(matches: &ArgMatches, terminal: &Terminal) -> usize {
+    // The validator only lets numbers through
+    let width = matches.value_of("width").map(|width| width.parse().unwrap())
+        .or_else(columns_from_env)
+        .or(terminal.width)
+        .unwrap_or(80);
+    if width == 0 { usize::MAX } else { width }
+}
+
+/// Colors on a terminal unless told otherwise, a plain --color means always
+fn color_option(matches: &ArgMatches) -> ColorOption {
+    match matches.value_of("color") {
+        // possible_values only lets known ones through
+        Some(when) => ColorOption::from_str(when).unwrap(),
+        None if matches.is_present("color") => ColorOption::Always,
+        None => ColorOption::Auto,
+    }
+}
+
+/// The colors to paint names with, if the output should be colored at all
+fn load_colors(matches: &ArgMatches, terminal: &Terminal, stderr: &mut dyn Write) -> Option<LsColors> {
+    let enabled = match color_option(matches) {
+        ColorOption::Never => false,
+        ColorOption::Auto => terminal.is_tty,
+        ColorOption::Always => true,
+    };
+    if !enabled {
+        return None;
+    }
+    // Bad colors don't stop the listing, it is just not colored
+    LsColors::from_env().map_err(|e| report(stderr, Ls.name(), &AppletError::failure(e))).ok()
+}
+
+/// The formats of file sizes and of allocated sizes (1K blocks by default, which is all -k asks for). Like in
+/// coreutils -h, --si and --block-size override the ones before them.
+fn size_formats(matches: &ArgMatches) -> (SizeFormat, SizeFormat) {
+    let indices = |flag| matches.indices_of(flag).into_iter().flatten();
+    let mut options: Vec<(usize, SizeFormat)> = Vec::new();
+    options.extend(indices("human-readable").map(|index| (index, SizeFormat::HUMAN)));
+    options.extend(indices("si").map(|index| (index, SizeFormat::SI)));
+    if let Some(values) = matches.values_of("block-size") {
+        // The validator only lets valid sizes through
+        options.extend(indices("block-size").zip(values).map(|(index, spec)| (index, SizeFormat::parse(spec).unwrap())));
+    }
+    match options.into_iter().max_by_key(|(index, _)| *index) {
+        Some((_, format)) => (format, format),
+        None => (SizeFormat::BYTES, SizeFormat::blocks(1024)),
+    }
+}
+
+/// -F, --file-type, -p and --indicator-style override each other, -F only counts when its WHEN applies
+fn indicator_style(matches: &ArgMatches, terminal: &Terminal) -> IndicatorStyle {
+    let mut chosen = last_flag(matches, &[("file-type", IndicatorStyle::FileType), ("slash", IndicatorStyle::Slash)]);
+    if let Some(indices) = matches.indices_of("indicator-style") {
+        // possible_values only lets known words through
+        let words = matches.values_of("indicator-style").into_iter().flatten();
+        chosen.extend(indices.zip(words).map(|(index, word)| (index, IndicatorStyle::from_str(word).unwrap())));
+    }
+    chosen.extend(last_flag(matches, &[("classify", IndicatorStyle::Classify)]));
+    if let (Some(indices), Some(whens)) = (matches.indices_of("classify-when"), matches.values_of("classify-when")) {
+        // possible_values only lets known ones through, and a --classify without one is always (see missing_values)
+        let classify = |when| match ColorOption::from_str(when).unwrap() {
+            ColorOption::Never => false,
+            ColorOption::Auto => terminal.is_tty,
+            ColorOption::Always => true,
+        };
+        // A WHEN that doesn't classify leaves the style to the options before it
+        chosen.extend(indices.zip(whens).filter(|(_, when)| classify(when)).map(|(index, _)| (index, IndicatorStyle::Classify)));
+    }
+    chosen.into_iter().max_by_key(|(index, _)| *index).map_or(IndicatorStyle::None, |(_, style)| style)
+}
+
+/// The last of -N, -b, -Q and --quoting-style, then QUOTING_STYLE, then shell-escape on a terminal
+fn quoting_style(matches: &ArgMatches, terminal: &Terminal) -> QuotingStyle {
+    let mut chosen = last_flag(matches, &[("literal", QuotingStyle::Literal), ("escape", QuotingStyle::Escape),
+                                          ("quote-name", QuotingStyle::C)]);
+    if let Some(indices) = matches.indices_of("quoting-style") {
+        // possible_values only lets known words through
+        let words = matches.values_of("quoting-style").into_iter().flatten();
+        chosen.extend(indices.zip(words).map(|(index, word)| (index, QuotingStyle::from_name(word).unwrap())));
+    }
+    let default = || {
+        let from_env = std::env::var("QUOTING_STYLE").ok().and_then(|style| QuotingStyle::from_name(&style));
+        from_env.unwrap_or(if terminal.is_tty { QuotingStyle::ShellEscape } else { QuotingStyle::Literal })
+    };
+    chosen.into_iter().max_by_key(|(index, _)| *index).map_or_else(default, |(_, style)| style)
 }
 
-fn build_display_fmt(matches: &ArgMatches) -> DisplayFormat {
+fn build_display_fmt(matches: &ArgMatches, terminal: &Terminal, colors: Option<LsColors>, times: TimeStyle) -> DisplayFormat {
+    let width = line_width(matches, terminal);
+    let (sizes, block_sizes) = size_formats(matches);
+    let layout = layout(matches, terminal);
+    let indicators = indicator_style(matches, terminal);
+    let colors = match indicators {
+        IndicatorStyle::FileType | IndicatorStyle::Classify => colors.map(LsColors::with_targets_known),
+        _ => colors,
+    };
+    // Long listings put the indicator of a link's target after it
+    let link_modes = colors.as_ref().is_some_and(LsColors::needs_link_modes)
+        || (layout == Layout::Long && matches!(indicators, IndicatorStyle::FileType | IndicatorStyle::Classify));
+    let quoting = quoting_style(matches, terminal);
+    let time = time_field(matches);
+    // Like coreutils, -u, -c and the like also sort by their time unless it is shown in a long listing
+    let sort_key = sort_key(matches).unwrap_or(match time {
+        TimeField::Modification => SortKey::Name,
+        _ if layout == Layout::Long => SortKey::Name,
+        _ => SortKey::Time,
+    });
+    let hide_control = last_flag(matches, &[("hide-control-chars", true), ("show-control-chars", false)])
+        .into_iter().max_by_key(|(index, _)| *index).map_or(terminal.is_tty, |(_, hide)| hide);
+    // Like coreutils, the escape style of ls escapes spaces too
+    let names = Quoting { style: quoting, hide_control, also: if quoting == QuotingStyle::Escape { " " } else { "" } };
     DisplayFormat {
-        show_hidden: matches.is_present("all"),
-        long_display: matches.is_present("long-display"),
-        color: ColorOption::from_str(&matches.value_of("color").unwrap_or("auto").to_string()).unwrap()
+        show_hidden: matches.is_present("all") || matches.is_present("almost-all"),
+        // The last of -a and -A wins
+        show_dot_entries: last_flag(matches, &[("all", true), ("almost-all", false)]).into_iter()
+            .max_by_key(|(index, _)| *index).is_some_and(|(_, dots)| dots),
+        directories_as_files: matches.is_present("directory"),
+        follow_operand_links: !matches.is_present("directory") && indicators != IndicatorStyle::Classify && layout != Layout::Long
+            && layout != Layout::Json,
+        layout,
+        width,
+        // The validator only lets numbers through. Tabs are only worth it on a line of limited width, and
+        // like coreutils colored names are always padded with spaces
+        tabsize: match width {
+            usize::MAX => 0,
+            _ if colors.is_some() => 0,
+            _ => matches.value_of("tabsize").map_or(8, |tabsize| tabsize.parse().unwrap()),
+        },
+        // -d lists no directory, so there is nothing to descend into
+        recursive: matches.is_present("recursive") && !matches.is_present("directory"),
+        dereference: matches.is_present("dereference"),
+        sort: SortOrder {
+            key: sort_key,
+            time,
+            reverse: matches.is_present("reverse"),
+            directories_first: matches.is_present("group-directories-first"),
+        },
+        colors,
+        long: LongOptions {
+            sizes, times, time,
+            show_owner: !matches.is_present("long-no-owner"),
+            show_group: !matches.is_present("long-no-group"),
+            show_author: matches.is_present("author"),
+            numeric_ids: matches.is_present("numeric-uid-gid"),
+        },
+        block_sizes,
+        show_blocks: matches.is_present("size"),
+        show_inodes: matches.is_present("inode"),
+        indicators,
+        names,
+        dirnames: Quoting { also: ":", ..names },
+        // Only where names line up in columns
+        align_quotes: (quoting == QuotingStyle::Shell || quoting == QuotingStyle::ShellEscape)
+            && (layout == Layout::Long || ((layout == Layout::Columns || layout == Layout::Across) && width != usize::MAX)),
+        link_modes,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io;
     use std::process::{Command, Output};
-    use super::{_ls_main, subcommand, build_display_fmt};
-    use std::ffi::OsStr;
+    use super::{_ls_main, color_option, list, IndicatorStyle, Layout, Ls, build_display_fmt};
+    use crate::librb::file::colors::LsColors;
+    use crate::librb::terminal::Terminal;
+    use crate::librb::quoting::{Quoting, QuotingStyle};
+    use crate::librb::time::TimeStyle;
+    use crate::core::applet::Applet;
+    use crate::core::error::{AppletError, EXIT_FAILURE, EXIT_TROUBLE};
+    use crate::core::pipe::{BrokenPipeWriter, EXIT_BROKEN_PIPE};
+    use crate::core::test_support::setup_dir;
+    use std::ffi::{OsStr, OsString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
     use std::str;
     use crate::applets::ls::ColorOption;
 
@@ -128,21 +888,42 @@ mod tests {
     fn test_arg_parse() {
         // ls -a -l /tmp/aaa
         let args: [&OsStr; 4] = [OsStr::new("ls"), OsStr::new("-a"), OsStr::new("-l"), OsStr::new("/tmp/aaa")];
-        let cmd = subcommand();
+        let cmd = Ls.subcommand();
         let matches = cmd.get_matches_from(args.iter());
-        let fmt = build_display_fmt(&matches);
+        let fmt = build_display_fmt(&matches, &Terminal::NONE, None, TimeStyle::LOCALE);
         assert!(fmt.show_hidden);
-        assert!(fmt.long_display);
-        assert!(fmt.color == ColorOption::Auto);
+        assert_eq!(fmt.layout, Layout::Long);
+        assert_eq!(color_option(&matches), ColorOption::Auto);
 
         // ls /tmp/aaa
         let args: [&OsStr; 2] = [OsStr::new("ls"), OsStr::new("/tmp/aaa")];
-        let cmd = subcommand();
+        let cmd = Ls.subcommand();
         let matches = cmd.get_matches_from(args.iter());
-        let fmt = build_display_fmt(&matches);
+        let fmt = build_display_fmt(&matches, &Terminal::NONE, None, TimeStyle::LOCALE);
         assert!(!fmt.show_hidden);
-        assert!(!fmt.long_display);
-        assert!(fmt.color == ColorOption::Auto);
+        assert_eq!(fmt.layout, Layout::OnePerLine);
+        assert_eq!(color_option(&matches), ColorOption::Auto);
+
+        // The last layout option wins, a terminal gets columns by default
+        let matches = Ls.subcommand().get_matches_from(["ls", "-l", "-m", "-w", "50", "-T", "4"].iter());
+        let fmt = build_display_fmt(&matches, &Terminal::NONE, None, TimeStyle::LOCALE);
+        assert_eq!(fmt.layout, Layout::Commas);
+        assert_eq!((fmt.width, fmt.tabsize), (50, 4));
+        let matches = Ls.subcommand().get_matches_from(["ls", "-w", "0", "-T", "4"].iter());
+        let fmt = build_display_fmt(&matches, &Terminal::NONE, None, TimeStyle::LOCALE);
+        assert_eq!((fmt.width, fmt.tabsize), (usize::MAX, 0));
+        let matches = Ls.subcommand().get_matches_from(["ls"].iter());
+        let fmt = build_display_fmt(&matches, &Terminal { is_tty: true, width: Some(100) }, None, TimeStyle::LOCALE);
+        assert_eq!(fmt.layout, Layout::Columns);
+        // A terminal gets names it can show, quoted the way a shell would take them
+        assert_eq!((fmt.names.style, fmt.names.hide_control), (QuotingStyle::ShellEscape, true));
+        let fmt = build_display_fmt(&Ls.subcommand().get_matches_from(["ls", "-Q", "-b", "--show-control-chars"].iter()), &Terminal::NONE, None, TimeStyle::LOCALE);
+        assert_eq!((fmt.names.style, fmt.names.hide_control), (QuotingStyle::Escape, false));
+        for (args, expected) in [(vec!["ls", "--color"], ColorOption::Always), (vec!["ls", "--color=if-tty"], ColorOption::Auto),
+                                 (vec!["ls", "--color=none"], ColorOption::Never)].iter() {
+            assert_eq!(color_option(&Ls.subcommand().get_matches_from(args.iter())), *expected);
+        }
+        assert!(Ls.subcommand().get_matches_from_safe(["ls", "-w", "x"].iter()).is_err());
     }
 
     fn run_cmd(cmd: &str) -> Output {
@@ -157,38 +938,344 @@ mod tests {
     fn test_print_dir() {
         // TODO: Create dir with files and check outputs
         // TODO: Create dir with files and check hidden outputs
-        let dir = "/tmp/rustybox-test/test_print_dir";
-        run_cmd(&format!("rm -rf {dir}; mkdir -p {dir}; touch {dir}/a {dir}/b {dir}/.c", dir=dir));
+        let dir: &str = &setup_dir("test_print_dir");
+        run_cmd(&format!("touch {dir}/a {dir}/b {dir}/.c", dir=dir));
         let args: [&OsStr; 2] = [OsStr::new("ls"), OsStr::new(dir)];
-        let cmd = subcommand();
+        let cmd = Ls.subcommand();
         let matches = cmd.get_matches_from(args.iter());
         let mut output : Vec<u8> = Vec::new();
-        assert!(_ls_main(Some(&matches), &mut output).is_ok());
-        println!("wtf {:?}", output);
-        let output = str::from_utf8(&output).unwrap();
-        match output {
-            "a b" => (),
-            "b a" => (),
-            _ => assert!(false),
-        }
+        assert!(_ls_main(&matches, &Terminal::NONE, &mut output, &mut io::sink()).is_ok());
+        assert_eq!(str::from_utf8(&output).unwrap(), "a\nb\n");
     }
     #[test]
     fn test_print_dir_hidden_file() {
         // TODO: Create dir with files and check outputs
         // TODO: Create dir with files and check hidden outputs
-        let dir = "/tmp/rustybox-test/test_print_dir_hidden_file";
-        run_cmd(&format!("rm -rf {dir}; mkdir -p {dir}; touch {dir}/a {dir}/.c", dir=dir));
+        let dir: &str = &setup_dir("test_print_dir_hidden_file");
+        run_cmd(&format!("touch {dir}/a {dir}/.c", dir=dir));
         let args: [&OsStr; 3] = [OsStr::new("ls"), OsStr::new("-a"), OsStr::new(dir)];
-        let cmd = subcommand();
+        let cmd = Ls.subcommand();
         let matches = cmd.get_matches_from(args.iter());
         let mut output : Vec<u8> = Vec::new();
-        assert!(_ls_main(Some(&matches), &mut output).is_ok());
-        println!("wtf {:?}", output);
-        let output = str::from_utf8(&output).unwrap();
-        match output {
-            "a .c" => (),
-            ".c a" => (),
-            _ => assert!(false),
+        assert!(_ls_main(&matches, &Terminal::NONE, &mut output, &mut io::sink()).is_ok());
+        assert_eq!(str::from_utf8(&output).unwrap(), ".\n..\n.c\na\n");
+    }
+
+    #[test]
+    fn test_print_dir_non_utf8_file_name() {
+        let dir = Path::new(&setup_dir("test_print_dir_non_utf8_file_name")).join(OsStr::from_bytes(b"\xff"));
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join(OsStr::from_bytes(b"caf\xe9")), "").unwrap();
+        let args: [&OsStr; 2] = [OsStr::new("ls"), dir.as_os_str()];
+        let matches = Ls.subcommand().get_matches_from(args.iter());
+        let mut output : Vec<u8> = Vec::new();
+        assert!(_ls_main(&matches, &Terminal::NONE, &mut output, &mut io::sink()).is_ok());
+        assert_eq!(output, b"caf\xe9\n");
+    }
+
+    #[test]
+    fn test_print_dir_broken_pipe() {
+        let dir: &str = &setup_dir("test_print_dir_broken_pipe");
+        run_cmd(&format!("touch {dir}/a", dir=dir));
+        let args: [&OsStr; 2] = [OsStr::new("ls"), OsStr::new(dir)];
+        let matches = Ls.subcommand().get_matches_from(args.iter());
+        assert_eq!(_ls_main(&matches, &Terminal::NONE, &mut BrokenPipeWriter, &mut io::sink()).unwrap_err(), AppletError::silent(EXIT_BROKEN_PIPE));
+    }
+
+    #[test]
+    fn test_long_display_matches_coreutils() {
+        let dir: &str = &setup_dir("test_long_display_matches_coreutils");
+        run_cmd(&format!("mkdir -p {dir}/d; echo hi > {dir}/a; head -c 12345 /dev/zero > {dir}/big; \
+                          ln -s a {dir}/l; ln {dir}/a {dir}/hard; touch -d 2000-01-01 {dir}/a; \
+                          touch {dir}/suid {dir}/sgid; chmod 4644 {dir}/suid; chmod 2755 {dir}/sgid; \
+                          mkdir {dir}/sticky; chmod 1777 {dir}/sticky", dir=dir));
+        let expected = run_cmd(&format!("LC_ALL=C ls -l {}", dir)).stdout;
+        let args: [&OsStr; 3] = [OsStr::new("ls"), OsStr::new("-l"), OsStr::new(dir)];
+        let matches = Ls.subcommand().get_matches_from(args.iter());
+        let mut output : Vec<u8> = Vec::new();
+        assert!(_ls_main(&matches, &Terminal::NONE, &mut output, &mut io::sink()).is_ok());
+        assert_eq!(str::from_utf8(&output).unwrap(), str::from_utf8(&expected).unwrap());
+    }
+
+    fn ls(args: &[&str]) -> (Result<(), AppletError>, String, String) {
+        let matches = Ls.get_matches(std::iter::once("ls").chain(args.iter().copied()).map(OsString::from).collect()).unwrap();
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let res = _ls_main(&matches, &Terminal::NONE, &mut stdout, &mut stderr);
+        (res, String::from_utf8(stdout).unwrap(), String::from_utf8(stderr).unwrap())
+    }
+
+    #[test]
+    fn test_recursive() {
+        // A single entry per directory keeps the output independent of the read_dir order
+        let dir: &str = &setup_dir("test_recursive");
+        run_cmd(&format!("mkdir -p {dir}/sub/deep {dir}/empty; touch {dir}/sub/deep/f", dir=dir));
+        let (res, stdout, _) = ls(&["-R", &format!("{}/sub", dir)]);
+        assert_eq!(res, Ok(()));
+        assert_eq!(stdout, format!("{dir}/sub:\ndeep\n\n{dir}/sub/deep:\nf\n", dir=dir));
+        let (_, stdout, _) = ls(&["-R", &format!("{}/empty", dir)]);
+        assert_eq!(stdout, format!("{}/empty:\n", dir));
+    }
+
+    #[test]
+    fn test_recursive_links() {
+        let dir: &str = &setup_dir("test_recursive_links");
+        run_cmd(&format!("mkdir -p {dir}/top/sub; touch {dir}/top/sub/f; ln -s ../top/sub {dir}/top/link; \
+                          mkdir -p {dir}/loop/sub; ln -s .. {dir}/loop/sub/up", dir=dir));
+        // Symbolic links to directories are only followed with -L
+        let (_, stdout, _) = ls(&["-R", &format!("{}/top", dir)]);
+        assert!(!stdout.contains("/link:"));
+        let (res, stdout, _) = ls(&["-R", "-L", &format!("{}/top", dir)]);
+        assert_eq!(res, Ok(()));
+        assert!(stdout.contains(&format!("\n{}/top/link:\nf\n", dir)));
+
+        let (res, stdout, stderr) = ls(&["-R", "-L", &format!("{}/loop", dir)]);
+        assert_eq!(res, Err(AppletError::silent(EXIT_TROUBLE)));
+        assert_eq!(stdout, format!("{dir}/loop:\nsub\n\n{dir}/loop/sub:\nup\n", dir=dir));
+        assert_eq!(stderr, format!("rustybox: ls: {}/loop/sub/up: not listing already-listed directory\n", dir));
+    }
+
+    #[test]
+    fn test_recursive_unreadable_directory() {
+        // Nothing is unreadable for root
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        // What an earlier run locked has to be unlocked before it can be removed
+        run_cmd("chmod -R u+rwx /tmp/rustybox-test/test_recursive_unreadable_directory");
+        let dir: &str = &setup_dir("test_recursive_unreadable_directory");
+        run_cmd(&format!("mkdir {dir}/locked; chmod 000 {dir}/locked", dir=dir));
+        let (res, stdout, stderr) = ls(&["-R", dir]);
+        assert_eq!(res, Err(AppletError::silent(EXIT_FAILURE)));
+        assert_eq!(stdout, format!("{}:\nlocked\n", dir));
+        assert_eq!(stderr, format!("rustybox: ls: {}/locked: Permission denied\n", dir));
+    }
+
+    #[test]
+    fn test_unsearchable_directory() {
+        // Nothing is unreadable for root
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        run_cmd("chmod -R u+rwx /tmp/rustybox-test/test_unsearchable_directory");
+        let dir: &str = &setup_dir("test_unsearchable_directory");
+        // The name can be read but not stat'ed
+        run_cmd(&format!("mkdir {dir}/names; touch {dir}/names/a; chmod 444 {dir}/names", dir=dir));
+        let (res, stdout, stderr) = ls(&[&format!("{}/names", dir)]);
+        assert_eq!(res, Err(AppletError::silent(EXIT_FAILURE)));
+        assert_eq!(stdout, "");
+        assert_eq!(stderr, format!("rustybox: ls: {}/names/a: Permission denied\n", dir));
+    }
+
+    #[test]
+    fn test_layouts_match_coreutils() {
+        let dir: &str = &setup_dir("test_layouts_match_coreutils");
+        run_cmd(&format!("mkdir -p {dir}/many {dir}/one {dir}/empty; cd {dir}/many; \
+                          touch alpha beta gamma delta epsilon zeta eta theta iota kappa lambda mu nu xi omicron pi rho; \
+                          touch {dir}/one/a-rather-long-name-that-does-not-fit-anywhere", dir=dir));
+        for args in [["-C", "-w", "40"], ["-x", "-w", "40"], ["-m", "-w", "40"], ["-C", "-w", "0"], ["-x", "-w", "7"],
+                     ["-C", "-w", "20"], ["-m", "-w", "20"], ["-C", "-T", "0"], ["-x", "-T", "3"], ["-1", "-w", "40"]].iter() {
+            for sub in ["many", "one", "empty"].iter() {
+                let path = format!("{}/{}", dir, sub);
+                let expected = run_cmd(&format!("LC_ALL=C ls {} {}", args.join(" "), path)).stdout;
+                let (res, stdout, _) = ls(&args.iter().copied().chain(std::iter::once(path.as_str())).collect::<Vec<_>>());
+                assert_eq!(res, Ok(()));
+                assert_eq!(stdout, str::from_utf8(&expected).unwrap(), "{:?} {}", args, sub);
+            }
+        }
+    }
+
+    #[test]
+    fn test_colors_match_coreutils() {
+        let dir: &str = &setup_dir("test_colors_match_coreutils");
+        run_cmd(&format!("cd {dir}; mkdir d ow st tw; chmod o+w ow tw; chmod +t st tw; \
+                          touch f x.tar s.JPG su; chmod +x x.tar; chmod u+s su; ln -s f l; ln -s d ld; ln -s nope orph; \
+                          mkfifo p", dir=dir));
+        let specs = ["", "ln=target:ex=01;32", "or=40;31;01:mi=05:*.jpg=35:*.tar=31", "di=:fi=1:*.JPG=32:*.jpg=33:ec=\x1b[m",
+                     "lc=\\e[:rc=^[[\\x6d:ec=\\033[0m:*.t\\x61r=\\063\\061"];
+        for spec in specs.iter() {
+            for args in [vec!["-C", "-w", "40"], vec!["-m"], vec!["-l"], vec!["-x", "-w", "20"], vec!["-lF"], vec!["-F", "-C", "-w", "40"]].iter() {
+                let expected = run_cmd(&format!("LC_ALL=C LS_COLORS='{}' ls --color=always {} {}", spec, args.join(" "), dir)).stdout;
+                let matches = Ls.subcommand().get_matches_from(std::iter::once("ls").chain(args.iter().copied()));
+                let fmt = build_display_fmt(&matches, &Terminal::NONE, Some(LsColors::parse(spec.as_bytes()).unwrap()), TimeStyle::LOCALE);
+                let mut stdout = Vec::new();
+                let res = list(&fmt, Ls.subcommand().get_matches_from(["ls", dir].iter()).values_of_os("directories"), &mut stdout, &mut io::sink());
+                assert_eq!(res, Ok(()));
+                assert_eq!(String::from_utf8(stdout).unwrap(), str::from_utf8(&expected).unwrap(), "{} {:?}", spec, args);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sizes_match_coreutils() {
+        let dir: &str = &setup_dir("test_sizes_match_coreutils");
+        run_cmd(&format!("mkdir -p {dir}/empty; cd {dir}; head -c 1023 /dev/zero > a; head -c 1536 /dev/zero > b; \
+                          head -c 1048577 /dev/zero > c; truncate -s 5G sparse; head -c 99999 /dev/zero > d", dir=dir));
+        for args in [vec!["-l"], vec!["-lh"], vec!["-l", "--si"], vec!["-ls", "--block-size=K"], vec!["-s"], vec!["-s", "-k"],
+                     vec!["-sh", "-C"], vec!["-l", "--block-size=KB"], vec!["-l", "--block-size=1K"], vec!["-lsh", "-k"],
+                     vec!["-s", "--si", "--block-size=MiB", "-m"], vec!["-s", "-h", "empty"]].iter() {
+            let expected = run_cmd(&format!("cd {}; LC_ALL=C ls {}", dir, args.join(" "))).stdout;
+            // Without an operand coreutils lists the directory it runs in
+            let operand = if args.contains(&"empty") { None } else { Some(dir.to_string()) };
+            let absolute: Vec<String> = args.iter().map(|arg| arg.replace("empty", &format!("{}/empty", dir))).chain(operand).collect();
+            let (res, stdout, _) = ls(&absolute.iter().map(String::as_str).collect::<Vec<_>>());
+            assert_eq!(res, Ok(()));
+            assert_eq!(stdout, str::from_utf8(&expected).unwrap(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_quoting_matches_coreutils() {
+        let dir: &str = &setup_dir("test_quoting_matches_coreutils");
+        run_cmd(&format!("mkdir -p {dir}/names {dir}/plain; cd {dir}/names; \
+                          touch plain 'a b' \"it's\" 'a\"b' '$x' '#hash' 'x~y' 'x=y' '{{' 'back\\slash' \"it's (x)\"; \
+                          touch \"$(printf 'tab\\tx')\" \"$(printf 'nl\\nx')\" \"$(printf 'ctl\\001x')\"; \
+                          mkdir 'a dir'; touch {dir}/plain/a {dir}/plain/b", dir=dir));
+        for style in ["literal", "shell", "shell-always", "shell-escape", "shell-escape-always", "c", "escape"].iter() {
+            let quoting = format!("--quoting-style={}", style);
+            for args in [vec!["-C", "-w", "60"], vec!["-x", "-w", "60"], vec!["-m"], vec!["-1"], vec!["-C", "-w", "0"],
+                         vec!["-1", "-q"], vec!["-R"]].iter() {
+                for sub in ["names", "plain"].iter() {
+                    let path = format!("{}/{}", dir, sub);
+                    let args: Vec<&str> = std::iter::once(quoting.as_str()).chain(args.iter().copied()).chain(std::iter::once(path.as_str())).collect();
+                    let expected = run_cmd(&format!("LC_ALL=C ls {} | od -c", args.join(" "))).stdout;
+                    let (res, stdout, _) = ls(&args);
+                    assert_eq!(res, Ok(()));
+                    let quoted = Quoting { style: QuotingStyle::ShellAlways, hide_control: false, also: "" }.quote(OsStr::new(&stdout)).text;
+                    let od = run_cmd(&format!("printf '%s' {} | od -c", String::from_utf8_lossy(&quoted))).stdout;
+                    assert_eq!(str::from_utf8(&od).unwrap(), str::from_utf8(&expected).unwrap(), "{:?}", args);
+                }
+            }
+        }
+        for args in [vec!["-N"], vec!["-b"], vec!["-Q"], vec!["-Q", "-N"], vec!["-b", "--quoting-style=c"], vec!["-q"],
+                     vec!["-q", "--show-control-chars"]].iter() {
+            let path = format!("{}/names", dir);
+            let args: Vec<&str> = args.iter().copied().chain(std::iter::once(path.as_str())).collect();
+            let expected = run_cmd(&format!("LC_ALL=C ls {}", args.join(" "))).stdout;
+            assert_eq!(ls(&args).1.as_bytes(), &expected[..], "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_indicators_match_coreutils() {
+        let dir: &str = &setup_dir("test_indicators_match_coreutils");
+        run_cmd(&format!("mkdir -p {dir}/d; cd {dir}; touch f x 'a b'; chmod +x x; ln -s d ld; ln -s x lx; \
+                          ln -s nope orph; mkfifo p; python3 -c \"import socket; socket.socket(socket.AF_UNIX).bind('s')\"", dir=dir));
+        for args in [vec!["-F"], vec!["-p"], vec!["--file-type"], vec!["--indicator-style=classify"], vec!["-F", "-p"],
+                     vec!["-p", "-F"], vec!["-F", "--indicator-style=none"], vec!["--classify=never"], vec!["-p", "--classify"],
+                     vec!["--classify", "-p"], vec!["-p", "--classify=never"], vec!["-lF"],
+                     vec!["-l", "--file-type"], vec!["-F", "-C", "-w", "30", "--quoting-style=shell"], vec!["-lp", "--quoting-style=shell"],
+                     vec!["-F", "-m", "-s"]].iter() {
+            let args: Vec<&str> = args.iter().copied().chain(std::iter::once(dir)).collect();
+            let expected = run_cmd(&format!("LC_ALL=C ls {}", args.join(" "))).stdout;
+            let (res, stdout, _) = ls(&args);
+            assert_eq!(res, Ok(()));
+            assert_eq!(stdout, str::from_utf8(&expected).unwrap(), "{:?}", args);
+        }
+        let matches = Ls.subcommand().get_matches_from(["ls", "--classify=auto"].iter());
+        assert_eq!(build_display_fmt(&matches, &Terminal::NONE, None, TimeStyle::LOCALE).indicators, IndicatorStyle::None);
+        assert_eq!(build_display_fmt(&matches, &Terminal { is_tty: true, width: None }, None, TimeStyle::LOCALE).indicators, IndicatorStyle::Classify);
+    }
+
+    #[test]
+    fn test_operands_match_coreutils() {
+        let dir: &str = &setup_dir("test_operands_match_coreutils");
+        run_cmd(&format!("mkdir -p {dir}/d1 {dir}/d2 {dir}/big; cd {dir}; touch f1 zf d1/a d1/.hidden d2/b; \
+                          head -c 100000 /dev/zero > big/x; ln -s d1 ld", dir=dir));
+        for args in [vec!["zf", "d2", "f1", "d1"], vec!["d1"], vec!["d1", "ld"], vec!["-l", "ld"], vec!["-F", "ld"], vec!["-d", "ld"],
+                     vec!["-R", "d1", "f1"], vec!["-a", "d1"], vec!["-A", "d1"], vec!["-a", "-A", "d1"], vec!["-A", "-a", "d1"],
+                     vec!["-d", "d1", "f1", "."], vec!["-dR", "."], vec!["-l", "f1", "big", "d1"], vec!["-s", "f1", "big"],
+                     vec!["-U", "d2", "f1", "d1", "zf"], vec!["-r", "d1", "d2", "f1", "zf"], vec!["-Ra", "d1"], vec!["-C", "f1", "zf"]].iter() {
+            let absolute: Vec<String> = args.iter().map(|arg| match arg.starts_with('-') {
+                true => arg.to_string(),
+                false => format!("{}/{}", dir, arg),
+            }).collect();
+            let expected = run_cmd(&format!("LC_ALL=C ls {}", absolute.join(" "))).stdout;
+            let (res, stdout, _) = ls(&absolute.iter().map(String::as_str).collect::<Vec<_>>());
+            assert_eq!(res, Ok(()), "{:?}", args);
+            assert_eq!(stdout, str::from_utf8(&expected).unwrap(), "{:?}", args);
+        }
+
+        // Bad operands are reported and skipped, the others are still listed
+        let (res, stdout, stderr) = ls(&[&format!("{}/nope", dir), &format!("{}/f1", dir), &format!("{}/d2", dir)]);
+        assert_eq!(res, Err(AppletError::silent(EXIT_TROUBLE)));
+        assert_eq!(stdout, format!("{dir}/f1\n\n{dir}/d2:\nb\n", dir=dir));
+        assert_eq!(stderr, format!("rustybox: ls: {}/nope: No such file or directory\n", dir));
+    }
+
+    #[test]
+    fn test_json() {
+        let dir: &str = &setup_dir("test_json");
+        run_cmd(&format!("mkdir -p {dir}/d; touch {dir}/f {dir}/d/g; ln -s d {dir}/ld", dir=dir));
+        let (res, stdout, _) = ls(&["--json", "-R", &format!("{}/f", dir), &format!("{}/ld", dir), &format!("{}/d", dir)]);
+        assert_eq!(res, Ok(()));
+        // Operands keep the names they were given, like links without being followed, and entries are found by their paths
+        let records: Vec<(&str, &str)> = stdout.lines().map(|line| {
+            let field = |name| line.split(&format!(r#""{}":""#, name)).nth(1).unwrap().split('"').next().unwrap();
+            (field("path"), field("type"))
+        }).collect();
+        assert_eq!(records, [(format!("{}/f", dir).as_str(), "file"), (&format!("{}/ld", dir), "symlink"), (&format!("{}/d/g", dir), "file")]);
+        // Operands are named by their last component like entries
+        assert!(stdout.starts_with(&format!(r#"{{"name":"f","path":"{}/f","#, dir)), "{}", stdout);
+        // The last format option wins
+        assert_eq!(ls(&["--json", "--format=single-column", dir]).1, "d\nf\nld\n");
+        assert_eq!(ls(&["-l", "--format=json", dir]).1.lines().count(), 3);
+    }
+
+    #[test]
+    fn test_long_columns_match_coreutils() {
+        let dir: &str = &setup_dir("test_long_columns_match_coreutils");
+        // An owner without a name shows as its id, chown only works as root but the comparison holds either way
+        run_cmd(&format!("mkdir -p {dir}/d; cd {dir}; touch a b; ln -s a l; chown 77:77 b", dir=dir));
+        for args in [vec!["-l"], vec!["-n"], vec!["-g"], vec!["-o"], vec!["-go"], vec!["-gn"], vec!["-l", "--author"], vec!["-o", "--author"],
+                     vec!["-i"], vec!["-li"], vec!["-is"], vec!["-isC"], vec!["-im"], vec!["-n", "-1"], vec!["-1", "-n"], vec!["-l", "-C"],
+                     vec!["-C", "-1"]].iter() {
+            let expected = run_cmd(&format!("LC_ALL=C ls {} {}", args.join(" "), dir)).stdout;
+            let (res, stdout, _) = ls(&args.iter().copied().chain(std::iter::once(dir)).collect::<Vec<_>>());
+            assert_eq!(res, Ok(()), "{:?}", args);
+            assert_eq!(stdout, str::from_utf8(&expected).unwrap(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_times_match_coreutils() {
+        let dir: &str = &setup_dir("test_times_match_coreutils");
+        run_cmd(&format!("cd {dir}; touch -d '2020-01-02 03:04:05.123456789' a; touch -d '-1 day' b; \
+                          touch -a -d '2019-05-05 05:05' b; touch c; touch -a -d '-2 hours' a", dir=dir));
+        for args in [vec!["-l"], vec!["-lu"], vec!["-lc"], vec!["-ltu"], vec!["-u"], vec!["-c"],
+                     vec!["--time=access"], vec!["-l", "--time=birth"], vec!["-t", "--time=birth"], vec!["-l", "--time-style=full-iso"],
+                     vec!["-l", "--time-style=long-iso"], vec!["-l", "--time-style=iso"], vec!["-l", "--time-style=locale"],
+                     vec!["-l", "--time-style=posix-iso"], vec!["--full-time"], vec!["-l", "--full-time", "--time-style=iso"],
+                     vec!["-l", "--time-style=+%Y %j %%N.%N"], vec!["-l", "--time-style=+old %Y\nrecent %H:%M"], vec!["--time-style=bogus"]].iter() {
+            let expected = run_cmd(&format!("LC_ALL=C ls '{}' {}", args.join("' '"), dir)).stdout;
+            let (res, stdout, _) = ls(&args.iter().copied().chain(std::iter::once(dir)).collect::<Vec<_>>());
+            assert_eq!(res, Ok(()), "{:?}", args);
+            assert_eq!(stdout, str::from_utf8(&expected).unwrap(), "{:?}", args);
+        }
+        // Newer coreutils also take mtime, to go back to the default
+        assert_eq!(ls(&["-u", "--time=mtime", dir]).1, ls(&[dir]).1);
+        // The time style is only checked when it is used
+        assert_eq!(ls(&["-l", "--time-style=bogus", dir]).0, Err(AppletError::new(EXIT_TROUBLE, "invalid argument 'bogus' for 'time style'".to_string())));
+    }
+
+    #[test]
+    fn test_sorting() {
+        let dir: &str = &setup_dir("test_sorting");
+        run_cmd(&format!("mkdir -p {dir}/d; cd {dir}; head -c 3 /dev/zero > b.x; head -c 2 /dev/zero > a.y; \
+                          head -c 1 /dev/zero > c; touch -d 2001-01-01 a.y; touch -d 2002-01-01 c; touch -d 2003-01-01 b.x; \
+                          touch -d 2000-01-01 d f10 f9", dir=dir));
+        for (args, expected) in [(vec![], "a.y b.x c d f10 f9"),
+                                 (vec!["-r"], "f9 f10 d c b.x a.y"),
+                                 (vec!["-t"], "b.x c a.y d f10 f9"),
+                                 (vec!["-S"], "d b.x a.y c f10 f9"),
+                                 (vec!["-X"], "c d f10 f9 b.x a.y"),
+                                 (vec!["-v"], "a.y b.x c d f9 f10"),
+                                 (vec!["-t", "-S"], "d b.x a.y c f10 f9"),
+                                 (vec!["-S", "--sort=version"], "a.y b.x c d f9 f10"),
+                                 (vec!["--sort", "time", "-r"], "f9 f10 d a.y c b.x"),
+                                 (vec!["--group-directories-first"], "d a.y b.x c f10 f9")].iter() {
+            let (res, stdout, _) = ls(&args.iter().copied().chain(std::iter::once(dir)).collect::<Vec<_>>());
+            assert_eq!(res, Ok(()));
+            assert_eq!(stdout, expected.replace(' ', "\n") + "\n", "{:?}", args);
         }
     }
 }
@@ -1,6 +1,63 @@
+use crate::core::applet::Applet;
+
+#[cfg(feature = "ls")]
 pub mod ls;
+#[cfg(feature = "touch")]
 pub mod touch;
+#[cfg(feature = "env")]
 pub mod env;
+#[cfg(feature = "cat")]
 pub mod cat;
+#[cfg(feature = "sleep")]
 pub mod sleep;
+#[cfg(feature = "true")]
 pub mod true_app;
+#[cfg(feature = "chmod")]
+pub mod chmod;
+#[cfg(feature = "stat")]
+pub mod stat;
+#[cfg(feature = "dircolors")]
+pub mod dircolors;
+
+/// Every applet compiled into rustybox, this is the only place an applet needs to be registered
+/// (besides its cargo feature)
+pub static APPLETS: &[&dyn Applet] = &[
+    #[cfg(feature = "ls")]
+    &ls::Ls,
+    #[cfg(feature = "touch")]
+    &touch::Touch,
+    #[cfg(feature = "env")]
+    &env::Env,
+    #[cfg(feature = "cat")]
+    &cat::Cat,
+    #[cfg(feature = "sleep")]
+    &sleep::Sleep,
+    #[cfg(feature = "true")]
+    &true_app::True,
+    #[cfg(feature = "chmod")]
+    &chmod::Chmod,
+    #[cfg(feature = "stat")]
+    &stat::Stat,
+    #[cfg(feature = "dircolors")]
+    &dircolors::Dircolors,
+];
+
+pub fn find_applet(name: &str) -> Option<&'static dyn Applet> {
+    APPLETS.iter().find(|applet| applet.name() == name).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{APPLETS, find_applet};
+
+    #[test]
+    fn test_registry() {
+        for (i, applet) in APPLETS.iter().enumerate() {
+            assert_eq!(applet.subcommand().get_name(), applet.name());
+            assert!(!applet.usage().is_empty());
+            assert!(APPLETS[..i].iter().all(|other| other.name() != applet.name()));
+            assert_eq!(find_applet(applet.name()).unwrap().name(), applet.name());
+        }
+        assert!(find_applet("rustybox").is_none());
+    }
+}
@@ -1,14 +1,28 @@
-use clap::{App, SubCommand, Arg, ArgMatches};
+use clap::{App, Arg, ArgMatches};
+use std::io::{Read, Write};
 use std::thread::sleep;
 use std::time::Duration;
+use crate::core::applet::Applet;
+use crate::core::error::AppletError;
+use crate::librb::quote::escape_invalid_utf8;
 
-pub fn subcommand() -> App<'static, 'static>  {
-    SubCommand::with_name("sleep")
-        .about("Print the current environment")
-        .help("Sleep for the total time of all arguments")
-        .arg(
+pub struct Sleep;
+
+impl Applet for Sleep {
+    fn name(&self) -> &'static str { "sleep" }
+    fn usage(&self) -> &'static str { "Sleep for the total time of all arguments" }
+
+    fn args(&self, cmd: App<'static, 'static>) -> App<'static, 'static> {
+        cmd.arg(
             Arg::with_name("times").multiple(true).index(1).required(true)
         )
+    }
+
+    fn main(&self, args: &ArgMatches, _stdin: &mut dyn Read, _stdout: &mut dyn Write, _stderr: &mut dyn Write) -> Result<(), AppletError> {
+        let time = get_time_to_sleep(args).map_err(AppletError::failure)?;
+        sleep(Duration::from_secs(time.into()));
+        Ok(())
+    }
 }
 
 fn parse(x: &str) -> Result<u32, String> {
@@ -34,14 +48,11 @@ fn sum_time_safe<'a>(values: &'a mut impl Iterator<Item = &'a str>) -> Result<u3
 }
 
 fn get_time_to_sleep(matches: &ArgMatches) -> Result<u32, String> {
-    sum_time_safe(&mut matches.values_of("times").unwrap())
-}
-
-pub fn sleep_main(matches: Option<&ArgMatches>) -> Result<(), String>{
-    // We have required arguments so this won't be none
-    let time = get_time_to_sleep(matches.unwrap())?;
-    sleep(Duration::from_secs(time.into()));
-    Ok(())
+    // OK because argument is required
+    let times = matches.values_of_os("times").unwrap()
+        .map(|x| x.to_str().ok_or_else(|| format!("Invalid arg {}", escape_invalid_utf8(x))))
+        .collect::<Result<Vec<&str>, String>>()?;
+    sum_time_safe(&mut times.into_iter())
 }
 
 #[cfg(test)]
@@ -0,0 +1,592 @@
+use clap::{App, Arg, ArgMatches};
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{Read, Write};
+use std::iter::{Copied, Peekable};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::slice::Iter;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::core::applet::Applet;
+use crate::core::error::{AppletError, report, EXIT_FAILURE};
+use crate::librb::file::filemeta::{FileMetadata, UidgidDisplay};
+use crate::librb::file::filetype::FileType;
+use crate::librb::file::fsinfo::FsInfo;
+use crate::librb::quote::escape_invalid_utf8;
+use crate::librb::quoting::{Quoting, QuotingStyle};
+use crate::librb::time::strftime;
+
+pub struct Stat;
+
+impl Applet for Stat {
+    fn name(&self) -> &'static str { "stat" }
+    fn usage(&self) -> &'static str { "Display file or file system status" }
+
+    fn args(&self, cmd: App<'static, 'static>) -> App<'static, 'static> {
+        cmd.arg(
+            Arg::with_name("dereference").short("-L").long("--dereference").help("follow links")
+        ).arg(
+            Arg::with_name("file-system").short("-f").long("--file-system").help("display file system status instead of file status")
+        ).arg(
+            Arg::with_name("format").short("-c").long("--format").value_name("FORMAT").takes_value(true)
+                .help("use the specified FORMAT instead of the default, with a newline after each use of FORMAT")
+        ).arg(
+            Arg::with_name("printf").long("--printf").value_name("FORMAT").takes_value(true).conflicts_with("format")
+                .help("like --format, but interpret backslash escapes and don't add a trailing newline")
+        ).arg(
+            Arg::with_name("json").long("--json").conflicts_with_all(&["format", "printf", "file-system"])
+                .help("print a JSON object per file, one per line, see JSON output in README.md")
+        ).arg(
+            Arg::with_name("files").value_name("FILE").multiple(true).index(1).required(true)
+        )
+    }
+
+    fn main(&self, args: &ArgMatches, _stdin: &mut dyn Read, stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), AppletError> {
+        stat_main(args, stdout, stderr)
+    }
+}
+
+const DEFAULT_FILE_FORMAT: &str = "  File: %N\n  Size: %-10s\tBlocks: %-10b IO Block: %-6o %F\n";
+const DEFAULT_DEVICE_LINE: &str = "Device: %Hd,%Ld\tInode: %-11i Links: %-5h Device type: %Hr,%Lr\n";
+const DEFAULT_INODE_LINE: &str = "Device: %Hd,%Ld\tInode: %-11i Links: %h\n";
+const DEFAULT_ACCESS_FORMAT: &str = "Access: (%04a/%10.10A)  Uid: (%5u/%8U)   Gid: (%5g/%8G)\n\
+                                     Access: %x\nModify: %y\nChange: %z\n Birth: %w\n";
+const DEFAULT_FS_FORMAT: &str = "  File: \"%n\"\n    ID: %-8i Namelen: %-7l Type: %T\n\
+                                 Block size: %-10s Fundamental block size: %S\n\
+                                 Blocks: Total: %-10b Free: %-10f Available: %a\n\
+                                 Inodes: Total: %-10c Free: %d\n";
+
+/// The flags, width and precision of one directive, the `-10` of `%-10s`
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// The bytes of a format, which like the names it shows needn't be UTF-8
+type Bytes<'a> = Peekable<Copied<Iter<'a, u8>>>;
+
+/// What a directive expands to, before the directive's Spec lays it out
+enum Field {
+    Text(String),
+    /// A file name, written as its bytes like coreutils does
+    Name(Vec<u8>),
+    Unsigned(u64),
+    Octal(u64),
+    Hex(u64),
+    /// Seconds since the epoch, the precision is the number of fraction digits
+    Seconds(SystemTime),
+}
+
+impl Spec {
+    fn pad(&self, text: &str) -> String {
+        match self.left {
+            true => format!("{:<width$}", text, width=self.width),
+            false => format!("{:>width$}", text, width=self.width),
+        }
+    }
+
+    /// Lay out a number like printf does: precision is the minimum digit count, 0 pads after the sign or prefix
+    fn number(&self, sign: &str, prefix: &str, digits: &str) -> String {
+        let digits = match self.precision {
+            Some(precision) => format!("{:0>precision$}", digits, precision=precision),
+            None => digits.to_string(),
+        };
+        let len = sign.len() + prefix.len() + digits.len();
+        if self.zero && !self.left && self.precision.is_none() && len < self.width {
+            return format!("{}{}{}{}", sign, prefix, "0".repeat(self.width - len), digits);
+        }
+        self.pad(&format!("{}{}{}", sign, prefix, digits))
+    }
+
+    fn sign(&self) -> &'static str {
+        match (self.plus, self.space) {
+            (true, _) => "+",
+            (false, true) => " ",
+            (false, false) => "",
+        }
+    }
+
+    /// Like `pad`, with the width and precision counted in bytes as printf does
+    fn pad_bytes(&self, name: &[u8]) -> Vec<u8> {
+        let name = &name[..self.precision.map_or(name.len(), |precision| precision.min(name.len()))];
+        let padding = vec![b' '; self.width.saturating_sub(name.len())];
+        match self.left {
+            true => [name, &padding].concat(),
+            false => [&padding, name].concat(),
+        }
+    }
+
+    fn format(&self, field: Field) -> Vec<u8> {
+        let text = match field {
+            Field::Text(text) => match self.precision {
+                Some(precision) => self.pad(&text.chars().take(precision).collect::<String>()),
+                None => self.pad(&text),
+            },
+            Field::Name(name) => return self.pad_bytes(&name),
+            Field::Unsigned(n) => self.number(self.sign(), "", &n.to_string()),
+            Field::Octal(n) => self.number("", if self.alternate && n != 0 { "0" } else { "" }, &format!("{:o}", n)),
+            Field::Hex(n) => self.number("", if self.alternate && n != 0 { "0x" } else { "" }, &format!("{:x}", n)),
+            Field::Seconds(time) => {
+                let (secs, nanos) = epoch_seconds(time);
+                let (sign, secs) = if secs < 0 { ("-", secs.unsigned_abs()) } else { (self.sign(), secs as u64) };
+                let digits = match self.precision {
+                    None | Some(0) => secs.to_string(),
+                    Some(precision) => {
+                        let fraction: String = format!("{:09}", nanos).chars().chain(std::iter::repeat('0')).take(precision).collect();
+                        format!("{}.{}", secs, fraction)
+                    }
+                };
+                Spec { precision: None, ..*self }.number(sign, "", &digits)
+            }
+        };
+        text.into_bytes()
+    }
+}
+
+/// Whole seconds (rounded down, even before the epoch) and the nanoseconds past them
+fn epoch_seconds(time: SystemTime) -> (i64, u32) {
+    let nanos = match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_nanos() as i128,
+        Err(before) => -(before.duration().as_nanos() as i128),
+    };
+    (nanos.div_euclid(1_000_000_000) as i64, nanos.rem_euclid(1_000_000_000) as u32)
+}
+
+fn human_time(time: SystemTime) -> String {
+    strftime(time, "%Y-%m-%d %H:%M:%S.%N %z")
+}
+
+fn parse_number(bytes: &mut Bytes) -> Option<usize> {
+    let digits: String = std::iter::from_fn(|| bytes.next_if(u8::is_ascii_digit)).map(char::from).collect();
+    digits.parse().ok()
+}
+
+fn parse_spec(bytes: &mut Bytes) -> Spec {
+    let mut spec = Spec::default();
+    while let Some(b) = bytes.peek() {
+        match b {
+            b'-' => spec.left = true,
+            b'0' => spec.zero = true,
+            b'+' => spec.plus = true,
+            b' ' => spec.space = true,
+            b'#' => spec.alternate = true,
+            // Thousands grouping and locale digits, nothing to do in the C locale
+            b'\'' | b'I' => (),
+            _ => break,
+        }
+        bytes.next();
+    }
+    spec.width = parse_number(bytes).unwrap_or(0);
+    if bytes.next_if_eq(&b'.').is_some() {
+        spec.precision = Some(parse_number(bytes).unwrap_or(0));
+    }
+    spec
+}
+
+/// Up to `max` digits of an escape sequence as a raw byte
+fn read_digits(bytes: &mut Bytes, radix: u32, max: usize) -> u8 {
+    let mut value: u32 = 0;
+    for _ in 0..max {
+        match bytes.peek().and_then(|b| char::from(*b).to_digit(radix)) {
+            Some(digit) => value = value * radix + digit,
+            None => break,
+        }
+        bytes.next();
+    }
+    value as u8
+}
+
+/// The escape sequence after a backslash in --printf, \NNN (octal) and \xHH give a raw byte
+fn unescape(bytes: &mut Bytes, out: &mut Vec<u8>) {
+    if bytes.peek().is_some_and(|b| (b'0'..=b'7').contains(b)) {
+        return out.push(read_digits(bytes, 8, 3));
+    }
+    let byte = match bytes.next() {
+        Some(b'a') => 0x07,
+        Some(b'b') => 0x08,
+        Some(b'e') => 0x1b,
+        Some(b'f') => 0x0c,
+        Some(b'n') => b'\n',
+        Some(b'r') => b'\r',
+        Some(b't') => b'\t',
+        Some(b'v') => 0x0b,
+        Some(b'x') if bytes.peek().is_some_and(u8::is_ascii_hexdigit) => read_digits(bytes, 16, 2),
+        Some(b) => {
+            if b != b'\\' && b != b'"' {
+                out.push(b'\\');
+            }
+            b
+        }
+        None => b'\\',
+    };
+    out.push(byte);
+}
+
+/// Expand the %-directives of `format`, `field` gives the value of a directive (and its H/L modifier),
+/// unknown directives become ?
+fn expand(format: &[u8], escapes: bool, field: &dyn Fn(char, Option<char>) -> Option<Field>) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut bytes = format.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'\\' if escapes => unescape(&mut bytes, &mut out),
+            b'%' => {
+                if bytes.next_if_eq(&b'%').is_some() {
+                    out.push(b'%');
+                    continue;
+                }
+                let spec = parse_spec(&mut bytes);
+                let modifier = bytes.next_if(|b| *b == b'H' || *b == b'L').map(char::from);
+                match bytes.next() {
+                    Some(directive) => match field(char::from(directive), modifier) {
+                        Some(value) => out.extend_from_slice(&spec.format(value)),
+                        None => out.push(b'?'),
+                    },
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+    }
+    out
+}
+
+fn describe_file_type(meta: &FileMetadata) -> &'static str {
+    match meta.file_type() {
+        FileType::RegularFile if meta.size() == 0 => "regular empty file",
+        FileType::RegularFile => "regular file",
+        FileType::Directory => "directory",
+        FileType::SymbolicLink => "symbolic link",
+        FileType::CharDevice => "character special file",
+        FileType::BlockDevice => "block special file",
+        FileType::Fifo => "fifo",
+        FileType::Socket => "socket",
+    }
+}
+
+/// The root of the file system `path` lives on, the last ancestor still on the same device
+fn mount_point(path: &Path) -> Option<PathBuf> {
+    let mut dir = fs::canonicalize(path).ok()?;
+    let dev = fs::metadata(&dir).ok()?.dev();
+    while let Some(parent) = dir.parent() {
+        if fs::metadata(parent).ok()?.dev() != dev {
+            break;
+        }
+        dir = parent.to_path_buf();
+    }
+    Some(dir)
+}
+
+fn major_minor(dev: u64, modifier: Option<char>) -> u64 {
+    match modifier {
+        Some('H') => libc::major(dev) as u64,
+        Some('L') => libc::minor(dev) as u64,
+        _ => dev,
+    }
+}
+
+/// `%N`, quoted unless it is the default format, with the target of symbolic links
+fn quoted_name(path: &OsStr, meta: &FileMetadata, quote: bool) -> Vec<u8> {
+    let quoting = Quoting { style: QuotingStyle::ShellEscapeAlways, hide_control: false, also: "" };
+    let quote = |name: &OsStr| match quote {
+        true => quoting.quote(name).text,
+        false => name.as_bytes().to_vec(),
+    };
+    match meta.link_target() {
+        Some(target) => [quote(path), b" -> ".to_vec(), quote(target.as_os_str())].concat(),
+        None => quote(path),
+    }
+}
+
+fn file_field(path: &OsStr, meta: &FileMetadata, quote: bool, directive: char, modifier: Option<char>) -> Option<Field> {
+    let name = |id: Option<String>| Field::Text(id.unwrap_or_else(|| "UNKNOWN".to_string()));
+    Some(match directive {
+        'a' => Field::Octal(meta.permissions().bits() as u64),
+        'A' => Field::Text(format!("{}{}", meta.file_type(), meta.permissions())),
+        'b' => Field::Unsigned(meta.blocks()),
+        'B' => Field::Unsigned(512),
+        'd' => Field::Unsigned(major_minor(meta.dev(), modifier)),
+        'D' => Field::Hex(meta.dev()),
+        'f' => Field::Hex(meta.mode() as u64),
+        'F' => Field::Text(describe_file_type(meta).to_string()),
+        'g' => Field::Unsigned(meta.gid() as u64),
+        'G' => name(meta.group().get_name()),
+        'h' => Field::Unsigned(meta.nlink()),
+        'i' => Field::Unsigned(meta.ino()),
+        'm' => Field::Name(mount_point(Path::new(path)).map_or_else(|| b"?".to_vec(), |dir| dir.into_os_string().into_vec())),
+        'n' => Field::Name(path.as_bytes().to_vec()),
+        'N' => Field::Name(quoted_name(path, meta, quote)),
+        'o' => Field::Unsigned(meta.blksize()),
+        'r' => Field::Unsigned(major_minor(meta.rdev(), modifier)),
+        's' => Field::Unsigned(meta.size()),
+        't' => Field::Hex(meta.rdev_major() as u64),
+        'T' => Field::Hex(meta.rdev_minor() as u64),
+        'u' => Field::Unsigned(meta.uid() as u64),
+        'U' => name(meta.owner().get_name()),
+        'w' => Field::Text(meta.btime().map_or_else(|| "-".to_string(), human_time)),
+        'W' => meta.btime().map_or(Field::Unsigned(0), Field::Seconds),
+        'x' => Field::Text(human_time(meta.atime())),
+        'X' => Field::Seconds(meta.atime()),
+        'y' => Field::Text(human_time(meta.mtime())),
+        'Y' => Field::Seconds(meta.mtime()),
+        'z' => Field::Text(human_time(meta.ctime())),
+        'Z' => Field::Seconds(meta.ctime()),
+        _ => return None,
+    })
+}
+
+fn fs_field(path: &OsStr, fs: &FsInfo, directive: char) -> Option<Field> {
+    Some(match directive {
+        'a' => Field::Unsigned(fs.blocks_available),
+        'b' => Field::Unsigned(fs.blocks),
+        'c' => Field::Unsigned(fs.files),
+        'd' => Field::Unsigned(fs.files_free),
+        'f' => Field::Unsigned(fs.blocks_free),
+        'i' => Field::Hex(fs.fsid),
+        'l' => Field::Unsigned(fs.name_max),
+        'n' => Field::Name(path.as_bytes().to_vec()),
+        's' => Field::Unsigned(fs.block_size),
+        'S' => Field::Unsigned(fs.fragment_size),
+        't' => Field::Hex(fs.fs_type),
+        'T' => Field::Text(fs.type_name()),
+        _ => return None,
+    })
+}
+
+/// The format given with -c or --printf
+struct UserFormat {
+    format: Vec<u8>,
+    escapes: bool,
+    newline: bool,
+}
+
+fn load(path: &OsStr, dereference: bool) -> Result<FileMetadata, AppletError> {
+    FileMetadata::load(Path::new(path), dereference).map_err(|e| AppletError::io(format!("cannot stat '{}'", escape_invalid_utf8(path)), &e))
+}
+
+fn stat_file(path: &OsStr, dereference: bool, format: &Option<UserFormat>) -> Result<Vec<u8>, AppletError> {
+    let meta = load(path, dereference)?;
+    let output = match format {
+        Some(user) => {
+            let mut output = expand(&user.format, user.escapes, &|d, m| file_field(path, &meta, true, d, m));
+            if user.newline {
+                output.push(b'\n');
+            }
+            output
+        }
+        None => {
+            let inode_line = if meta.is_device() { DEFAULT_DEVICE_LINE } else { DEFAULT_INODE_LINE };
+            let default = [DEFAULT_FILE_FORMAT, inode_line, DEFAULT_ACCESS_FORMAT].concat();
+            expand(default.as_bytes(), false, &|d, m| file_field(path, &meta, false, d, m))
+        }
+    };
+    Ok(output)
+}
+
+fn stat_json(path: &OsStr, dereference: bool) -> Result<Vec<u8>, AppletError> {
+    let meta = load(path, dereference)?;
+    Ok(format!("{}\n", meta.to_json(Path::new(path))).into_bytes())
+}
+
+fn stat_file_system(path: &OsStr, format: &Option<UserFormat>) -> Result<Vec<u8>, AppletError> {
+    let fs = FsInfo::for_path(Path::new(path))
+        .map_err(|e| AppletError::io(format!("cannot read file system information for '{}'", escape_invalid_utf8(path)), &e))?;
+    let output = match format {
+        Some(user) => {
+            let mut output = expand(&user.format, user.escapes, &|d, _| fs_field(path, &fs, d));
+            if user.newline {
+                output.push(b'\n');
+            }
+            output
+        }
+        None => expand(DEFAULT_FS_FORMAT.as_bytes(), false, &|d, _| fs_field(path, &fs, d)),
+    };
+    Ok(output)
+}
+
+fn stat_main(args: &ArgMatches, stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), AppletError> {
+    let format = match (args.value_of_os("format"), args.value_of_os("printf")) {
+        (Some(format), _) => Some(UserFormat { format: format.as_bytes().to_vec(), escapes: false, newline: true }),
+        (None, Some(format)) => Some(UserFormat { format: format.as_bytes().to_vec(), escapes: true, newline: false }),
+        (None, None) => None,
+    };
+    let mut failed = false;
+    // OK because argument is required
+    for path in args.values_of_os("files").unwrap() {
+        let output = if args.is_present("json") {
+            stat_json(path, args.is_present("dereference"))
+        } else if args.is_present("file-system") {
+            stat_file_system(path, &format)
+        } else {
+            stat_file(path, args.is_present("dereference"), &format)
+        };
+        match output {
+            Ok(output) => stdout.write_all(&output)?,
+            Err(e) => {
+                report(stderr, Stat.name(), &e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Err(AppletError::silent(EXIT_FAILURE));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stat_main, Stat};
+    use crate::core::applet::Applet;
+    use crate::core::error::{AppletError, EXIT_FAILURE};
+    use crate::core::test_support::setup_dir;
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{symlink, MetadataExt, PermissionsExt};
+    use std::path::Path;
+    use std::process::Command;
+
+    fn run(args: &[&str]) -> (Result<(), AppletError>, Vec<u8>, String) {
+        let matches = Stat.subcommand().get_matches_from(std::iter::once("stat").chain(args.iter().copied()).map(OsStr::new));
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let res = stat_main(&matches, &mut stdout, &mut stderr);
+        (res, stdout, String::from_utf8(stderr).unwrap())
+    }
+
+    fn output(args: &[&str]) -> String {
+        let (res, stdout, _) = run(args);
+        assert_eq!(res, Ok(()));
+        String::from_utf8(stdout).unwrap()
+    }
+
+    fn coreutils_stat(args: &[&str]) -> String {
+        let out = Command::new("stat").args(args).env("LC_ALL", "C").output().expect("failed to execute process");
+        String::from_utf8(out.stdout).unwrap()
+    }
+
+    /// A 5 byte file with mode 0640, modified at 1234567890.5
+    fn create_file(dir: &str) -> String {
+        let file = format!("{}/file", dir);
+        fs::write(&file, "hello").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+        Command::new("touch").args(["-m", "-d", "@1234567890.5", &file]).output().expect("failed to execute process");
+        file
+    }
+
+    #[test]
+    fn test_stat_format_directives() {
+        let dir = setup_dir("test_stat_format_directives");
+        let file = create_file(&dir);
+        let meta = fs::metadata(&file).unwrap();
+        assert_eq!(output(&["-c", "%a %A %s %Y %.3Y %n %F %h %f", &file]),
+                   format!("640 -rw-r----- 5 1234567890 1234567890.500 {} regular file 1 81a0\n", file));
+        assert_eq!(output(&["-c", "%i %u %g %b %o %d %D", &file]),
+                   format!("{} {} {} {} {} {} {:x}\n", meta.ino(), meta.uid(), meta.gid(), meta.blocks(), meta.blksize(), meta.dev(), meta.dev()));
+        assert_eq!(output(&["-c", "%F %a", &dir]), "directory 755\n");
+        assert_eq!(output(&["-c", "%t,%T %Hr,%Lr %F", "/dev/null"]), "1,3 1,3 character special file\n");
+        assert_eq!(output(&["-c", "%m", "/proc/self"]), "/proc\n");
+        assert_eq!(output(&["-c", "%q|%", &file]), "?|%\n");
+    }
+
+    #[test]
+    fn test_stat_format_flags() {
+        let dir = setup_dir("test_stat_format_flags");
+        let file = create_file(&dir);
+        assert_eq!(output(&["-c", "[%-4s][%4s][%04s][%.3s][%+s][%#a][%#f][%08.3Y]", &file]),
+                   "[5   ][   5][0005][005][+5][0640][0x81a0][1234567890.500]\n");
+        assert_eq!(output(&["-c", "[%5.2F][%-5.3A]", &file]), "[   re][-rw  ]\n");
+        assert_eq!(output(&["--printf", "%s\\t%%\\x41\\101\\n\\q", &file]), "5\t%AA\n\\q");
+        // -c doesn't interpret escapes
+        assert_eq!(output(&["-c", "%s\\n", &file]), "5\\n\n");
+    }
+
+    #[test]
+    fn test_stat_non_utf8() {
+        let dir = setup_dir("test_stat_non_utf8");
+        let file = Path::new(&dir).join(OsStr::from_bytes(b"caf\xe9"));
+        fs::write(&file, "hello").unwrap();
+        let args: [&OsStr; 4] = [OsStr::new("stat"), OsStr::new("--printf"), OsStr::from_bytes(b"\xff%s %n\\xfe"), file.as_os_str()];
+        let matches = Stat.subcommand().get_matches_from(args.iter());
+        let mut stdout = Vec::new();
+        assert_eq!(stat_main(&matches, &mut stdout, &mut Vec::new()), Ok(()));
+        assert_eq!(stdout, [b"\xff5 ", file.as_os_str().as_bytes(), b"\xfe"].concat());
+    }
+
+    #[test]
+    fn test_stat_links() {
+        let dir = setup_dir("test_stat_links");
+        let file = create_file(&dir);
+        let link = format!("{}/link", dir);
+        symlink("file", &link).unwrap();
+        assert_eq!(output(&["-c", "%N %F", &link]), format!("'{}' -> 'file' symbolic link\n", link));
+        assert_eq!(output(&["-L", "-c", "%N %F %s", &link]), format!("'{}' regular file 5\n", link));
+        assert_eq!(output(&["-c", "%N", &file]), format!("'{}'\n", file));
+        // Control characters are escaped, like coreutils' shell-escape-always style
+        let name = format!("{}/n\nl", dir);
+        fs::write(&name, "").unwrap();
+        let tab_link = format!("{}/tab\tlink", dir);
+        symlink("it's", &tab_link).unwrap();
+        assert_eq!(output(&["-c", "%N", &name]), format!("'{}/n'$'\\n''l'\n", dir));
+        assert_eq!(output(&["-c", "%N", &tab_link]), format!("'{}/tab'$'\\t''link' -> \"it's\"\n", dir));
+    }
+
+    #[test]
+    fn test_stat_default_matches_coreutils() {
+        let dir = setup_dir("test_stat_default_matches_coreutils");
+        let file = create_file(&dir);
+        let link = format!("{}/link", dir);
+        symlink("file", &link).unwrap();
+        for args in [vec![file.as_str()], vec![link.as_str()], vec!["-L", link.as_str()], vec![dir.as_str()], vec!["/dev/null"]].iter() {
+            // Reading a fresh link bumps its access time once (relatime), let that happen before comparing
+            output(args);
+            assert_eq!(output(args), coreutils_stat(args), "{:?}", args);
+        }
+        // The free counts may change between the two calls
+        let args = ["-f", "-c", "%n %i %l %t %T %s %S %b %c", "/proc"];
+        assert_eq!(output(&args), coreutils_stat(&args));
+    }
+
+    #[test]
+    fn test_stat_file_system() {
+        let out = output(&["-f", "-c", "%T %t %n", "/proc"]);
+        assert_eq!(out, "proc 9fa0 /proc\n");
+        let out = output(&["-f", "/proc"]);
+        assert!(out.starts_with("  File: \"/proc\"\n    ID: "));
+        assert!(out.contains("Namelen: 255     Type: proc\n"));
+    }
+
+    #[test]
+    fn test_stat_missing_file() {
+        let dir = setup_dir("test_stat_missing_file");
+        let file = create_file(&dir);
+        let missing = format!("{}/missing", dir);
+        let (res, stdout, stderr) = run(&["-c", "%s", &missing, &file]);
+        assert_eq!(res, Err(AppletError::silent(EXIT_FAILURE)));
+        assert_eq!(stdout, b"5\n");
+        assert_eq!(stderr, format!("rustybox: stat: cannot stat '{}': No such file or directory\n", missing));
+    }
+
+    #[test]
+    fn test_stat_json() {
+        let dir = setup_dir("test_stat_json");
+        let file = create_file(&dir);
+        let link = format!("{}/link", dir);
+        symlink("file", &link).unwrap();
+        let out = output(&["--json", &file, &link]);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(&format!(r#"{{"name":"file","path":"{}","type":"file","#, file)), "{}", lines[0]);
+        assert!(lines[0].contains(r#","size":5,"#));
+        assert!(lines[1].contains(r#""type":"symlink","#) && lines[1].ends_with(r#""target":"file"}"#), "{}", lines[1]);
+        assert!(output(&["--json", "-L", &link]).contains(r#""type":"file","#));
+        assert!(Stat.subcommand().get_matches_from_safe(["stat", "--json", "-c", "%n", "x"].iter()).is_err());
+    }
+}
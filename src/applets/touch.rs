@@ -1,12 +1,17 @@
-use clap::{App, Arg, ArgMatches, Values, SubCommand};
+use clap::{App, Arg, ArgMatches, OsValues};
 use std::fs::File;
 use std::io;
+use std::io::{Read, Write};
+use crate::core::applet::Applet;
+use crate::core::error::{AppletError, report, EXIT_FAILURE};
+use crate::librb::quote::escape_invalid_utf8;
+use std::path::Path;
 
 struct TouchArguments {
     create_file: bool,
 }
 
-fn touch_file(name: String, args: &TouchArguments) -> Result<(), io::Error> {
+fn touch_file(name: impl AsRef<Path>, args: &TouchArguments) -> Result<(), io::Error> {
     match args.create_file {
         true => File::create(name)?,
         false => File::open(name)?,
@@ -14,46 +19,64 @@ fn touch_file(name: String, args: &TouchArguments) -> Result<(), io::Error> {
     Ok(())
 }
 
-pub fn subcommand() -> App<'static, 'static> {
-    SubCommand::with_name("touch")
-        .about("Touch a file")
-        .arg(
+pub struct Touch;
+
+impl Applet for Touch {
+    fn name(&self) -> &'static str { "touch" }
+    fn usage(&self) -> &'static str { "Touch a file" }
+
+    fn args(&self, cmd: App<'static, 'static>) -> App<'static, 'static> {
+        cmd.arg(
             Arg::with_name("create").short("-c").long("--no-create").takes_value(false).help("don't create file")
         ).arg(
-        Arg::with_name("files").multiple(true).index(1).required(true)
-    )
+            Arg::with_name("files").multiple(true).index(1).required(true)
+        )
+    }
+
+    fn main(&self, args: &ArgMatches, _stdin: &mut dyn Read, _stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), AppletError> {
+        touch_main(args, stderr)
+    }
 }
 
-fn touch_files(files: Values, args: &TouchArguments) -> Result<(), String>{
+fn touch_files(files: OsValues, args: &TouchArguments, stderr: &mut dyn Write) -> Result<(), AppletError>{
+    let mut failed = false;
     for f in  files {
-        touch_file(f.to_string(), args).or(Err(format!("Failed to touch {}", f).to_string()))?;
+        if let Err(e) = touch_file(f, args) {
+            report(stderr, Touch.name(), &AppletError::io(escape_invalid_utf8(f), &e));
+            failed = true;
+        }
+    }
+    if failed {
+        return Err(AppletError::silent(EXIT_FAILURE));
     }
     Ok(())
 }
 
-pub fn touch_main(args: Option<&ArgMatches>) -> Result<(), String>{
-    // OK because argument is required
-    let args = args.unwrap();
+fn touch_main(args: &ArgMatches, stderr: &mut dyn Write) -> Result<(), AppletError>{
     let ta = TouchArguments { create_file: !args.is_present("create")};
-    let files = args.values_of("files").unwrap();
-    touch_files(files, &ta)
+    // OK because argument is required
+    let files = args.values_of_os("files").unwrap();
+    touch_files(files, &ta, stderr)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{touch_main, touch_file, TouchArguments, subcommand};
+    use super::{touch_main, touch_file, TouchArguments, Touch};
+    use crate::core::error::{AppletError, EXIT_FAILURE};
+    use crate::core::applet::Applet;
     use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
     use std::path::Path;
     use std::process::Command;
 
     #[test]
     fn test_create_touch() {
-        let res = touch_file("/tmp/should_create".to_string(), &TouchArguments { create_file: true }).err();
+        let res = touch_file("/tmp/should_create", &TouchArguments { create_file: true }).err();
         assert!(res.is_none());
-        let res = touch_file("/tmp/should_fail".to_string(), &TouchArguments { create_file: false }).err().unwrap();
+        let res = touch_file("/tmp/should_fail", &TouchArguments { create_file: false }).err().unwrap();
         // Should ENOENT (2)
         assert_eq!(res.raw_os_error().unwrap(), 2);
-        let res = touch_file("/tmp/should_create".to_string(), &TouchArguments { create_file: false }).err();
+        let res = touch_file("/tmp/should_create", &TouchArguments { create_file: false }).err();
         assert!(res.is_none());
     }
 
@@ -61,17 +84,19 @@ mod tests {
     fn test_subcommand_fail_to_create() {
         let name = "hello";
         let args: [&OsStr; 3] = [OsStr::new("touch"), OsStr::new("-c"), OsStr::new(name)];
-        let cmd = subcommand();
+        let cmd = Touch.subcommand();
         let matches = cmd.get_matches_from(args.iter());
         assert!(matches.is_present("create"));
-        assert_eq!(touch_main(Some(&matches)).unwrap_err(), format!("Failed to touch {}", name));
+        let mut stderr: Vec<u8> = Vec::new();
+        assert_eq!(touch_main(&matches, &mut stderr).unwrap_err(), AppletError::silent(EXIT_FAILURE));
+        assert_eq!(stderr, format!("rustybox: touch: {}: No such file or directory\n", name).as_bytes());
     }
 
     #[test]
     fn test_subcommand_create() {
         let name = "/tmp/chello";
         let args: [&OsStr; 2] = [OsStr::new("touch"), OsStr::new(name)];
-        let cmd = subcommand();
+        let cmd = Touch.subcommand();
         let matches = cmd.get_matches_from(args.iter());
         Command::new("sh")
             .arg("-c")
@@ -80,7 +105,16 @@ mod tests {
             .expect("failed to execute process");
         assert!(!Path::new(name).exists());
         assert!(!matches.is_present("create"));
-        assert!(touch_main(Some(&matches)).is_ok());
+        assert!(touch_main(&matches, &mut std::io::sink()).is_ok());
+        assert!(Path::new(name).exists());
+    }
+
+    #[test]
+    fn test_touch_non_utf8_file_name() {
+        let name = OsStr::from_bytes(b"/tmp/rustybox-touch-test-caf\xe9");
+        let _ = std::fs::remove_file(name);
+        let matches = Touch.subcommand().get_matches_from(vec![OsStr::new("touch"), name]);
+        assert!(touch_main(&matches, &mut std::io::sink()).is_ok());
         assert!(Path::new(name).exists());
     }
 }
@@ -1,10 +1,15 @@
-use clap::{App, Arg, ArgMatches, Values, SubCommand};
+use clap::ArgMatches;
+use std::io::{Read, Write};
+use crate::core::applet::Applet;
+use crate::core::error::AppletError;
 
-pub fn subcommand() -> App<'static, 'static> {
-    SubCommand::with_name("true")
-        .about("return success")
-}
+pub struct True;
+
+impl Applet for True {
+    fn name(&self) -> &'static str { "true" }
+    fn usage(&self) -> &'static str { "return success" }
 
-pub fn true_main(args: Option<&ArgMatches>) -> Result<(), String>{
-    Ok(())
+    fn main(&self, _args: &ArgMatches, _stdin: &mut dyn Read, _stdout: &mut dyn Write, _stderr: &mut dyn Write) -> Result<(), AppletError> {
+        Ok(())
+    }
 }
@@ -0,0 +1,43 @@
+use clap::{App, ArgMatches, SubCommand};
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::os::unix::io::RawFd;
+use crate::core::args::fill_missing_values;
+use crate::core::error::AppletError;
+
+/// A single rustybox command, reachable both as `rustybox NAME` and through a link named NAME
+pub trait Applet: Sync {
+    fn name(&self) -> &'static str;
+
+    /// One line description of the applet, shown by --help
+    fn usage(&self) -> &'static str;
+
+    /// Add the applet's arguments to its (already named) clap definition
+    fn args(&self, cmd: App<'static, 'static>) -> App<'static, 'static> {
+        cmd
+    }
+
+    fn subcommand(&self) -> App<'static, 'static> {
+        self.args(SubCommand::with_name(self.name()).about(self.usage()))
+    }
+
+    /// Long options that may come without their value, with the value they then stand for. clap records where an
+    /// option was only through its values, so these are filled in before parsing for the last option to win.
+    fn missing_values(&self) -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+
+    /// Parse the applet's command line, `args` starting with the name it was invoked as
+    fn get_matches(&self, args: Vec<OsString>) -> clap::Result<ArgMatches<'static>> {
+        self.subcommand().get_matches_from_safe(fill_missing_values(args, self.missing_values()))
+    }
+
+    fn main(&self, args: &ArgMatches, stdin: &mut dyn Read, stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), AppletError>;
+
+    /// `main` where `stdin` and `stdout` are the open files `fds`, which applets copying data may hand to the kernel.
+    /// Only the caller owning the process's stdio knows that.
+    fn main_with_fds(&self, args: &ArgMatches, stdin: &mut dyn Read, stdout: &mut dyn Write, stderr: &mut dyn Write,
+                     _fds: (RawFd, RawFd)) -> Result<(), AppletError> {
+        self.main(args, stdin, stdout, stderr)
+    }
+}
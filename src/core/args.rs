@@ -1,6 +1,35 @@
 use clap::App;
+use std::ffi::OsString;
 
 pub fn add_generic_info(cmd: App<'static, 'static>) -> App<'static, 'static> {
     cmd.version("0.0.1") .author("Efi Weiss <valmarelox@gmail.com>")
 }
 
+/// `args` with each of the long `options` given without a value spelled with the value it stands for, like
+/// --classify as --classify=always. Arguments after -- are operands and stay as they are.
+pub fn fill_missing_values(args: Vec<OsString>, options: &[(&str, &str)]) -> Vec<OsString> {
+    let mut operands = false;
+    args.into_iter().map(|arg| {
+        operands = operands || arg == "--";
+        match options.iter().find(|(option, _)| !operands && arg == *option) {
+            Some((option, value)) => format!("{}={}", option, value).into(),
+            None => arg,
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fill_missing_values;
+    use std::ffi::OsString;
+
+    #[test]
+    fn test_fill_missing_values() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        let options = [("--classify", "always")];
+        assert_eq!(fill_missing_values(args(&["ls", "-p", "--classify", "--classify=never", "--classifyx"]), &options),
+                   args(&["ls", "-p", "--classify=always", "--classify=never", "--classifyx"]));
+        assert_eq!(fill_missing_values(args(&["ls", "--", "--classify"]), &options), args(&["ls", "--", "--classify"]));
+    }
+}
+
@@ -0,0 +1,132 @@
+use std::ffi::CStr;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use crate::core::pipe::EXIT_BROKEN_PIPE;
+
+/// Some operand failed but the applet kept going
+pub const EXIT_FAILURE: i32 = 1;
+/// Bad command line
+pub const EXIT_USAGE: i32 = 2;
+/// Serious trouble, like an operand ls can't access at all. coreutils shares the status with bad usage
+pub const EXIT_TROUBLE: i32 = EXIT_USAGE;
+
+/// Why an applet failed and the exit status rustybox should terminate with.
+/// A message-less error was already reported by the applet itself.
+#[derive(Debug, PartialEq)]
+pub struct AppletError {
+    status: i32,
+    message: Option<String>,
+}
+
+impl AppletError {
+    pub fn new(status: i32, message: String) -> AppletError {
+        AppletError { status, message: Some(message) }
+    }
+
+    pub fn failure(message: String) -> AppletError {
+        AppletError::new(EXIT_FAILURE, message)
+    }
+
+    pub fn usage(message: String) -> AppletError {
+        AppletError::new(EXIT_USAGE, message)
+    }
+
+    pub fn silent(status: i32) -> AppletError {
+        AppletError { status, message: None }
+    }
+
+    /// `file: strerror`, the way coreutils reports a failing operand
+    pub fn io(file: impl fmt::Display, err: &io::Error) -> AppletError {
+        if err.kind() == io::ErrorKind::BrokenPipe {
+            return AppletError::broken_pipe();
+        }
+        AppletError::failure(format!("{}: {}", file, strerror(err)))
+    }
+
+    /// Whoever reads our output is gone, nothing is left to report to
+    pub fn broken_pipe() -> AppletError {
+        AppletError::silent(EXIT_BROKEN_PIPE)
+    }
+
+    pub fn status(&self) -> i32 {
+        self.status
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+impl fmt::Display for AppletError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message().unwrap_or(""))
+    }
+}
+
+impl From<io::Error> for AppletError {
+    fn from(err: io::Error) -> AppletError {
+        if err.kind() == io::ErrorKind::BrokenPipe {
+            return AppletError::broken_pipe();
+        }
+        AppletError::failure(strerror(&err))
+    }
+}
+
+impl From<String> for AppletError {
+    fn from(message: String) -> AppletError {
+        AppletError::failure(message)
+    }
+}
+
+/// The bare libc description of an error, without std's " (os error N)" suffix
+pub fn strerror(err: &io::Error) -> String {
+    match err.raw_os_error() {
+        // strerror always returns a valid C string, even for unknown errno values
+        Some(errno) => unsafe { CStr::from_ptr(libc::strerror(errno)) }.to_string_lossy().into_owned(),
+        None => err.to_string(),
+    }
+}
+
+/// Print `rustybox: applet: message` to stderr
+pub fn report(stderr: &mut dyn Write, applet: &str, message: &dyn fmt::Display) {
+    // Nowhere left to report a failure to write to stderr
+    let _ = writeln!(stderr, "rustybox: {}: {}", applet, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AppletError, report, strerror, EXIT_FAILURE, EXIT_USAGE, EXIT_BROKEN_PIPE};
+    use std::io;
+
+    #[test]
+    fn test_strerror() {
+        assert_eq!(strerror(&io::Error::from_raw_os_error(libc::ENOENT)), "No such file or directory");
+        assert_eq!(strerror(&io::Error::other("custom")), "custom");
+    }
+
+    #[test]
+    fn test_error_statuses() {
+        let err = AppletError::io("missing", &io::Error::from_raw_os_error(libc::ENOENT));
+        assert_eq!(err.status(), EXIT_FAILURE);
+        assert_eq!(err.message(), Some("missing: No such file or directory"));
+        assert_eq!(AppletError::usage("bad".to_string()).status(), EXIT_USAGE);
+        assert_eq!(AppletError::silent(3).message(), None);
+        assert_eq!(AppletError::from("oops".to_string()), AppletError::failure("oops".to_string()));
+    }
+
+    #[test]
+    fn test_broken_pipe_is_silent() {
+        let epipe = || io::Error::from_raw_os_error(libc::EPIPE);
+        assert_eq!(AppletError::from(epipe()), AppletError::silent(EXIT_BROKEN_PIPE));
+        assert_eq!(AppletError::io("file", &epipe()), AppletError::silent(EXIT_BROKEN_PIPE));
+        assert_eq!(EXIT_BROKEN_PIPE, 141);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut stderr: Vec<u8> = Vec::new();
+        report(&mut stderr, "cat", &AppletError::io("a", &io::Error::from_raw_os_error(libc::EISDIR)));
+        assert_eq!(stderr, b"rustybox: cat: a: Is a directory\n");
+    }
+}
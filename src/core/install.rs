@@ -0,0 +1,130 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use crate::core::applet::Applet;
+use crate::core::error::AppletError;
+use std::fs;
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+/// Like busybox, `--install` is only recognized as the first argument: rustybox --install [-s] [-f] DIR
+pub const INSTALL_FLAG: &str = "--install";
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name(INSTALL_FLAG)
+        .bin_name("rustybox --install")
+        .about("create links to rustybox for every applet in DIR")
+        .arg(
+            Arg::with_name("symlinks").short("-s").help("create symbolic links instead of hard links")
+        ).arg(
+            Arg::with_name("force").short("-f").long("--force").help("replace existing files")
+        ).arg(
+            Arg::with_name("dir").value_name("DIR").index(1).required(true)
+    )
+}
+
+pub struct InstallOptions {
+    symlinks: bool,
+    force: bool,
+}
+
+impl InstallOptions {
+    pub fn build(matches: &ArgMatches) -> InstallOptions {
+        InstallOptions {
+            symlinks: matches.is_present("symlinks"),
+            force: matches.is_present("force"),
+        }
+    }
+}
+
+fn install_link(exe: &Path, link: &Path, opts: &InstallOptions) -> io::Result<()> {
+    if fs::symlink_metadata(link).is_ok() {
+        if !opts.force {
+            return Ok(());
+        }
+        fs::remove_file(link)?;
+    }
+    if opts.symlinks {
+        symlink(exe, link)
+    } else {
+        fs::hard_link(exe, link)
+    }
+}
+
+/// Link every applet in `applets` to `exe` inside `dir`, leaving existing files alone unless forced
+pub fn install_applets(applets: &[&dyn Applet], exe: &Path, dir: &Path, opts: &InstallOptions) -> Result<(), AppletError> {
+    for applet in applets {
+        let link = dir.join(applet.name());
+        install_link(exe, &link, opts).map_err(|e| AppletError::io(link.display(), &e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{subcommand, install_applets, InstallOptions};
+    use crate::applets::APPLETS;
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::path::PathBuf;
+    use crate::core::test_support::setup_dir;
+
+    /// A test directory with a stand-in rustybox executable to install
+    fn setup_install_dir(name: &str) -> PathBuf {
+        let dir = PathBuf::from(setup_dir(name));
+        fs::write(dir.join("rustybox"), "exe").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_install_args() {
+        let args: [&OsStr; 3] = [OsStr::new("--install"), OsStr::new("-s"), OsStr::new("/bin")];
+        let matches = subcommand().get_matches_from(args.iter());
+        assert_eq!(matches.value_of("dir"), Some("/bin"));
+        let opts = InstallOptions::build(&matches);
+        assert!(opts.symlinks);
+        assert!(!opts.force);
+
+        let args: [&OsStr; 1] = [OsStr::new("--install")];
+        assert!(subcommand().get_matches_from_safe(args.iter()).is_err());
+    }
+
+    #[test]
+    fn test_install_hard_links() {
+        let dir = &setup_install_dir("test_install_hard_links");
+        let exe = dir.join("rustybox");
+        let opts = InstallOptions { symlinks: false, force: false };
+        assert!(install_applets(APPLETS, &exe, dir, &opts).is_ok());
+        for applet in APPLETS.iter() {
+            let link = dir.join(applet.name());
+            assert_eq!(fs::read_to_string(&link).unwrap(), "exe");
+            assert!(!fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        }
+    }
+
+    #[test]
+    fn test_install_symlinks() {
+        let dir = &setup_install_dir("test_install_symlinks");
+        let exe = dir.join("rustybox");
+        let opts = InstallOptions { symlinks: true, force: false };
+        assert!(install_applets(APPLETS, &exe, dir, &opts).is_ok());
+        for applet in APPLETS.iter() {
+            assert_eq!(fs::read_link(dir.join(applet.name())).unwrap(), exe);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "ls", feature = "cat"))]
+    fn test_install_existing_files() {
+        let dir = &setup_install_dir("test_install_existing_files");
+        let exe = dir.join("rustybox");
+        fs::write(dir.join("ls"), "old").unwrap();
+        let opts = InstallOptions { symlinks: true, force: false };
+        assert!(install_applets(APPLETS, &exe, dir, &opts).is_ok());
+        assert_eq!(fs::read_to_string(dir.join("ls")).unwrap(), "old");
+        assert_eq!(fs::read_link(dir.join("cat")).unwrap(), exe);
+
+        let opts = InstallOptions { symlinks: true, force: true };
+        assert!(install_applets(APPLETS, &exe, dir, &opts).is_ok());
+        assert_eq!(fs::read_link(dir.join("ls")).unwrap(), exe);
+    }
+}
@@ -1 +1,7 @@
-pub mod args;
\ No newline at end of file
+pub mod applet;
+pub mod args;
+pub mod error;
+pub mod install;
+pub mod pipe;
+#[cfg(test)]
+pub mod test_support;
@@ -0,0 +1,27 @@
+/// What a shell reports for a process killed by SIGPIPE
+pub const EXIT_BROKEN_PIPE: i32 = 128 + libc::SIGPIPE;
+
+/// The Rust runtime ignores SIGPIPE, so writing to a closed pipe fails with EPIPE instead of
+/// killing the process. Restore the default so `rustybox cat big | head -1` exits quietly like coreutils,
+/// writers that still see EPIPE are mapped to a silent EXIT_BROKEN_PIPE by AppletError.
+pub fn restore_default_sigpipe() {
+    // Resetting a signal disposition to its default has no memory safety implications
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
+
+/// A writer whose reader went away, for testing how applets handle EPIPE
+#[cfg(test)]
+pub struct BrokenPipeWriter;
+
+#[cfg(test)]
+impl std::io::Write for BrokenPipeWriter {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::from_raw_os_error(libc::EPIPE))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
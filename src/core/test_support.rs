@@ -0,0 +1,9 @@
+use std::fs;
+
+/// A fresh, empty /tmp/rustybox-test/`name` for a test to work in
+pub fn setup_dir(name: &str) -> String {
+    let dir = format!("/tmp/rustybox-test/{}", name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
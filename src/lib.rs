@@ -0,0 +1,18 @@
+// The applets and the code they share. Being a library, the parts of librb that a build with only some of
+// the applets (see the features in Cargo.toml) doesn't call are still public API rather than dead code.
+pub mod librb;
+pub mod applets;
+pub mod core;
+
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "strum")]
+extern crate strum;
+#[cfg(feature = "strum_macros")]
+#[macro_use] extern crate strum_macros;
+#[cfg(feature = "bitflags")]
+#[macro_use]
+extern crate bitflags;
+
+#[cfg(feature = "num_enum")]
+extern crate num_enum;
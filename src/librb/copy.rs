@@ -0,0 +1,162 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::mem::ManuallyDrop;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::ptr;
+
+/// Bytes asked of the kernel per call, and the size of the buffer when the data has to go through userspace
+pub const CHUNK: usize = 128 * 1024;
+
+/// The ways the kernel can copy between two files by itself, in the order they are tried
+#[derive(Clone, Copy, Debug)]
+enum Transfer {
+    /// Between regular files, reflinking where the file system can
+    CopyFileRange,
+    /// From a regular file to anything
+    Sendfile,
+    /// From or to a pipe
+    Splice,
+}
+
+const TRANSFERS: [Transfer; 3] = [Transfer::CopyFileRange, Transfer::Sendfile, Transfer::Splice];
+
+/// One call, the number of bytes it copied
+fn transfer(how: Transfer, from: RawFd, to: RawFd) -> io::Result<usize> {
+    // Null offsets make the calls use and advance the offsets of the files, so whatever copies next goes on from there
+    let copied = unsafe {
+        match how {
+            Transfer::CopyFileRange => libc::copy_file_range(from, ptr::null_mut(), to, ptr::null_mut(), CHUNK, 0),
+            Transfer::Sendfile => libc::sendfile(to, from, ptr::null_mut(), CHUNK),
+            Transfer::Splice => libc::splice(from, ptr::null_mut(), to, ptr::null_mut(), CHUNK, libc::SPLICE_F_MOVE),
+        }
+    };
+    if copied < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(copied as usize)
+}
+
+/// The errors of a transfer that doesn't work for these two files, or on this kernel
+fn is_unsupported(err: &io::Error) -> bool {
+    matches!(err.raw_os_error(), Some(libc::EINVAL) | Some(libc::ENOSYS) | Some(libc::EXDEV) | Some(libc::EOPNOTSUPP)
+             | Some(libc::EBADF) | Some(libc::EPERM))
+}
+
+/// Which side of a copy failed, reading goes wrong for one file while writing goes wrong for all of them
+#[derive(Debug)]
+pub enum CopyError {
+    Read(io::Error),
+    Write(io::Error),
+}
+
+/// Copy everything from `reader` to `writer` through a large buffer
+pub fn copy_buffered(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<u64, CopyError> {
+    let mut buf = vec![0; CHUNK];
+    let mut copied = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(copied),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(CopyError::Read(e)),
+        };
+        writer.write_all(&buf[..n]).map_err(CopyError::Write)?;
+        copied += n as u64;
+    }
+}
+
+/// Copy the rest of `from` to `to` without the data going through userspace when the kernel can do it for these
+/// files, through a buffer otherwise. The descriptors stay open.
+pub fn copy_fd(from: RawFd, to: RawFd) -> Result<u64, CopyError> {
+    let mut copied = 0;
+    'transfers: for how in TRANSFERS.iter() {
+        let mut started = false;
+        loop {
+            match transfer(*how, from, to) {
+                // Files like those in /proc claim to be empty, only reading them tells
+                Ok(0) if !started => break,
+                Ok(0) => return Ok(copied),
+                Ok(n) => {
+                    copied += n as u64;
+                    started = true;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) if is_unsupported(&e) => break,
+                // The error doesn't say which file it is about, the buffer copy runs into it again and does
+                Err(_) => break 'transfers,
+            }
+        }
+    }
+    // Borrowed, dropping them would close the descriptors
+    let mut reader = ManuallyDrop::new(unsafe { File::from_raw_fd(from) });
+    let mut writer = ManuallyDrop::new(unsafe { File::from_raw_fd(to) });
+    Ok(copied + copy_buffered(&mut *reader, &mut *writer)?)
+}
+
+/// Test data for whatever copies files: lines of every byte value, NULs and invalid UTF-8 included, more than a
+/// few chunks of them, the last one without a newline
+#[cfg(test)]
+pub fn binary() -> Vec<u8> {
+    (0..CHUNK * 3 + 17).map(|i| (i * 7 % 256) as u8).chain(b"\n\nend".iter().copied()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{binary, copy_buffered, copy_fd};
+    use crate::core::test_support::setup_dir;
+    use std::fs;
+    use std::fs::File;
+    use std::io::{Seek, SeekFrom, Write};
+    use std::os::unix::io::AsRawFd;
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn test_copy_fd() {
+        let dir: &str = &setup_dir("test_copy_fd");
+        let data = binary();
+        fs::write(format!("{}/in", dir), &data).unwrap();
+
+        // File to file
+        let input = File::open(format!("{}/in", dir)).unwrap();
+        let output = File::create(format!("{}/out", dir)).unwrap();
+        assert_eq!(copy_fd(input.as_raw_fd(), output.as_raw_fd()).unwrap(), data.len() as u64);
+        assert_eq!(fs::read(format!("{}/out", dir)).unwrap(), data);
+
+        // What is left after the offset, appended after what is there
+        let mut input = File::open(format!("{}/in", dir)).unwrap();
+        input.seek(SeekFrom::Start(10)).unwrap();
+        let mut output = fs::OpenOptions::new().append(true).open(format!("{}/out", dir)).unwrap();
+        output.write_all(b"-").unwrap();
+        assert_eq!(copy_fd(input.as_raw_fd(), output.as_raw_fd()).unwrap(), data.len() as u64 - 10);
+        assert_eq!(fs::read(format!("{}/out", dir)).unwrap(), [&data[..], b"-", &data[10..]].concat());
+
+        // File to pipe
+        let mut child = Command::new("sh").arg("-c").arg(format!("cat > {}/to-pipe", dir)).stdin(Stdio::piped()).spawn().unwrap();
+        let stdin = child.stdin.take().unwrap();
+        copy_fd(File::open(format!("{}/in", dir)).unwrap().as_raw_fd(), stdin.as_raw_fd()).unwrap();
+        drop(stdin);
+        child.wait().unwrap();
+        assert_eq!(fs::read(format!("{}/to-pipe", dir)).unwrap(), data);
+
+        // Pipe to file
+        let mut child = Command::new("cat").arg(format!("{}/in", dir)).stdout(Stdio::piped()).spawn().unwrap();
+        let output = File::create(format!("{}/from-pipe", dir)).unwrap();
+        assert_eq!(copy_fd(child.stdout.as_ref().unwrap().as_raw_fd(), output.as_raw_fd()).unwrap(), data.len() as u64);
+        child.wait().unwrap();
+        assert_eq!(fs::read(format!("{}/from-pipe", dir)).unwrap(), data);
+
+        // Files that claim to be empty
+        let output = File::create(format!("{}/proc", dir)).unwrap();
+        copy_fd(File::open("/proc/self/mounts").unwrap().as_raw_fd(), output.as_raw_fd()).unwrap();
+        assert!(fs::metadata(format!("{}/proc", dir)).unwrap().len() > 0);
+    }
+
+    #[test]
+    fn test_copy_buffered() {
+        let data = binary();
+        let mut output = Vec::new();
+        assert_eq!(copy_buffered(&mut &data[..], &mut output).unwrap(), data.len() as u64);
+        assert_eq!(output, data);
+    }
+}
@@ -0,0 +1,216 @@
+use std::ffi::CString;
+
+/// The database `dircolors -p` prints and ls falls back to without LS_COLORS
+pub const DEFAULT_DATABASE: &str = "\
+# Terminals that understand the color escapes, the entries below only apply to them
+COLORTERM ?*
+TERM Eterm
+TERM ansi
+TERM *color*
+TERM con[0-9]*x[0-9]*
+TERM cons25
+TERM console
+TERM cygwin
+TERM *direct*
+TERM dtterm
+TERM gnome
+TERM hurd
+TERM jfbterm
+TERM konsole
+TERM kterm
+TERM linux
+TERM linux-c
+TERM mlterm
+TERM putty
+TERM rxvt*
+TERM screen*
+TERM st
+TERM terminator
+TERM tmux*
+TERM vt100
+TERM xterm*
+
+# File types, as SGR parameters (01 bold, 3x foreground, 4x background)
+RESET 0
+DIR 01;34
+LINK 01;36 # 'target' colors links like what they point to
+MULTIHARDLINK 00
+FIFO 40;33
+SOCK 01;35
+DOOR 01;35
+BLK 40;33;01
+CHR 40;33;01
+ORPHAN 40;31;01
+MISSING 00
+SETUID 37;41
+SETGID 30;43
+CAPABILITY 00
+STICKY_OTHER_WRITABLE 30;42
+OTHER_WRITABLE 34;42
+STICKY 37;44
+EXEC 01;32
+
+# Archives
+.tar 01;31
+.tgz 01;31
+.zip 01;31
+.z 01;31
+.gz 01;31
+.lz 01;31
+.lz4 01;31
+.lzma 01;31
+.xz 01;31
+.txz 01;31
+.zst 01;31
+.tzst 01;31
+.bz2 01;31
+.tbz2 01;31
+.deb 01;31
+.rpm 01;31
+.jar 01;31
+.rar 01;31
+.7z 01;31
+.cpio 01;31
+
+# Images and video
+.jpg 01;35
+.jpeg 01;35
+.gif 01;35
+.bmp 01;35
+.png 01;35
+.svg 01;35
+.tif 01;35
+.tiff 01;35
+.webp 01;35
+.xcf 01;35
+.mov 01;35
+.mpg 01;35
+.mpeg 01;35
+.mkv 01;35
+.webm 01;35
+.mp4 01;35
+.avi 01;35
+.flv 01;35
+
+# Audio
+.aac 00;36
+.flac 00;36
+.m4a 00;36
+.mid 00;36
+.mp3 00;36
+.ogg 00;36
+.opus 00;36
+.wav 00;36
+
+# Backups and leftovers
+*~ 00;90
+*# 00;90
+.bak 00;90
+.old 00;90
+.orig 00;90
+.swp 00;90
+.tmp 00;90
+.rpmnew 00;90
+.rpmsave 00;90
+.dpkg-dist 00;90
+.dpkg-old 00;90
+";
+
+/// The keywords of a dircolors database and the LS_COLORS codes they stand for
+const KEYWORDS: &[(&str, &str)] = &[
+    ("NORMAL", "no"), ("NORM", "no"), ("FILE", "fi"), ("RESET", "rs"), ("DIR", "di"), ("LNK", "ln"), ("LINK", "ln"),
+    ("SYMLINK", "ln"), ("ORPHAN", "or"), ("MISSING", "mi"), ("FIFO", "pi"), ("PIPE", "pi"), ("SOCK", "so"),
+    ("BLK", "bd"), ("BLOCK", "bd"), ("CHR", "cd"), ("CHAR", "cd"), ("DOOR", "do"), ("EXEC", "ex"), ("LEFT", "lc"),
+    ("LEFTCODE", "lc"), ("RIGHT", "rc"), ("RIGHTCODE", "rc"), ("END", "ec"), ("ENDCODE", "ec"), ("SUID", "su"),
+    ("SETUID", "su"), ("SGID", "sg"), ("SETGID", "sg"), ("STICKY", "st"), ("OTHER_WRITABLE", "ow"), ("OWR", "ow"),
+    ("STICKY_OTHER_WRITABLE", "tw"), ("OWT", "tw"), ("CAPABILITY", "ca"), ("MULTIHARDLINK", "mh"), ("CLRTOEOL", "cl"),
+];
+
+fn fnmatch(pattern: &str, s: &str) -> bool {
+    match (CString::new(pattern), CString::new(s)) {
+        // fnmatch only reads both strings
+        (Ok(pattern), Ok(s)) => unsafe { libc::fnmatch(pattern.as_ptr(), s.as_ptr(), 0) == 0 },
+        _ => false,
+    }
+}
+
+/// Where a database line stands with respect to the TERM and COLORTERM lines before it
+#[derive(PartialEq)]
+enum TermState {
+    /// No TERM line yet, entries apply everywhere
+    Global,
+    /// Some line of the current group of TERM lines matched
+    Matched,
+    /// Entries after a matching group
+    Yes,
+    No,
+}
+
+/// The LS_COLORS value a dircolors database describes, e.g. "di=01;34:*.tar=01;31:". Only entries for a
+/// terminal matching `terminal` (TERM and COLORTERM) are taken, all of them without it. Errors are
+/// "LINE: reason".
+pub fn compile_database(database: &str, terminal: Option<(&str, &str)>) -> Result<String, String> {
+    let mut ls_colors = String::new();
+    let mut state = TermState::Global;
+    for (i, line) in database.lines().enumerate() {
+        // A comment starts a line or follows whitespace
+        let line = match line.char_indices().find(|(at, c)| *c == '#' && (*at == 0 || line[..*at].ends_with(char::is_whitespace))) {
+            Some((at, _)) => &line[..at],
+            None => line,
+        }.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (keyword, arg) = match line.find(char::is_whitespace) {
+            Some(at) => (&line[..at], line[at..].trim_start()),
+            None => return Err(format!("{}: invalid line;  missing second token", i + 1)),
+        };
+        let term_line = match keyword.to_ascii_uppercase().as_str() {
+            "TERM" => Some(terminal.map(|(term, _)| term)),
+            "COLORTERM" => Some(terminal.map(|(_, colorterm)| colorterm)),
+            _ => None,
+        };
+        if let Some(value) = term_line {
+            if value.is_none_or(|value| fnmatch(arg, value)) {
+                state = TermState::Matched;
+            } else if state != TermState::Matched {
+                state = TermState::No;
+            }
+            continue;
+        }
+        if state == TermState::Matched {
+            state = TermState::Yes;
+        }
+        if state == TermState::No {
+            continue;
+        }
+        if let Some(extension) = keyword.strip_prefix('.') {
+            ls_colors += &format!("*.{}={}:", extension, arg);
+        } else if keyword.starts_with('*') {
+            ls_colors += &format!("{}={}:", keyword, arg);
+        } else if let Some((_, code)) = KEYWORDS.iter().find(|(name, _)| name.eq_ignore_ascii_case(keyword)) {
+            ls_colors += &format!("{}={}:", code, arg);
+        }
+        // Anything else (COLOR, OPTIONS, EIGHTBIT) is a leftover of older databases and means nothing
+    }
+    Ok(ls_colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compile_database, DEFAULT_DATABASE};
+
+    #[test]
+    fn test_compile_database() {
+        let database = "TERM foo*\nDIR 01;35 # comment\n.txt 01;31\nTERM xterm\nLINK target\n*~ 00;90\n# comment\nCOLOR tty\n";
+        assert_eq!(compile_database(database, Some(("foox", ""))).unwrap(), "di=01;35:*.txt=01;31:");
+        assert_eq!(compile_database(database, Some(("xterm", ""))).unwrap(), "ln=target:*~=00;90:");
+        assert_eq!(compile_database(database, Some(("dumb", ""))).unwrap(), "");
+        assert_eq!(compile_database(database, None).unwrap(), "di=01;35:*.txt=01;31:ln=target:*~=00;90:");
+        assert_eq!(compile_database("dir 1\nBOGUS 2\nexec a#b\n", None).unwrap(), "di=1:ex=a#b:");
+        assert_eq!(compile_database("DIR 1\nDIR\n", None).unwrap_err(), "2: invalid line;  missing second token");
+        let default = compile_database(DEFAULT_DATABASE, Some(("dumb", "truecolor"))).unwrap();
+        assert!(default.starts_with("rs=0:di=01;34:ln=01;36:"));
+        assert!(default.contains(":*.tar=01;31:"));
+    }
+}
@@ -0,0 +1,403 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::env;
+use std::iter::{Copied, Peekable};
+use std::os::unix::ffi::OsStrExt;
+use std::slice::Iter;
+use std::str::FromStr;
+use crate::librb::file::colordb::{compile_database, DEFAULT_DATABASE};
+use crate::librb::file::filemeta::FileMetadata;
+use crate::librb::file::filetype::FileType;
+use crate::librb::file::permissions::PermissionsMask;
+
+/// What the entries of LS_COLORS color, the names are its two letter codes
+#[derive(EnumString, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Indicator {
+    #[strum(serialize = "lc")]
+    Left,
+    #[strum(serialize = "rc")]
+    Right,
+    #[strum(serialize = "ec")]
+    End,
+    #[strum(serialize = "rs")]
+    Reset,
+    #[strum(serialize = "no")]
+    Normal,
+    #[strum(serialize = "fi")]
+    File,
+    #[strum(serialize = "di")]
+    Directory,
+    #[strum(serialize = "ln")]
+    Link,
+    #[strum(serialize = "pi")]
+    Fifo,
+    #[strum(serialize = "so")]
+    Socket,
+    #[strum(serialize = "bd")]
+    BlockDevice,
+    #[strum(serialize = "cd")]
+    CharDevice,
+    #[strum(serialize = "mi")]
+    Missing,
+    #[strum(serialize = "or")]
+    Orphan,
+    #[strum(serialize = "ex")]
+    Executable,
+    #[strum(serialize = "do")]
+    Door,
+    #[strum(serialize = "su")]
+    SetUid,
+    #[strum(serialize = "sg")]
+    SetGid,
+    #[strum(serialize = "st")]
+    Sticky,
+    #[strum(serialize = "ow")]
+    OtherWritable,
+    #[strum(serialize = "tw")]
+    StickyOtherWritable,
+    #[strum(serialize = "ca")]
+    Capability,
+    #[strum(serialize = "mh")]
+    MultiHardLink,
+    #[strum(serialize = "cl")]
+    ClearToEol,
+}
+
+/// What ls colors with when LS_COLORS leaves an indicator out, the same as coreutils
+const BUILTIN_INDICATORS: &[(Indicator, &str)] = &[
+    (Indicator::Left, "\x1b["),
+    (Indicator::Right, "m"),
+    (Indicator::Reset, "0"),
+    (Indicator::Directory, "01;34"),
+    (Indicator::Link, "01;36"),
+    (Indicator::Fifo, "33"),
+    (Indicator::Socket, "01;35"),
+    (Indicator::BlockDevice, "01;33"),
+    (Indicator::CharDevice, "01;33"),
+    (Indicator::Executable, "01;32"),
+    (Indicator::Door, "01;35"),
+    (Indicator::SetUid, "37;41"),
+    (Indicator::SetGid, "30;43"),
+    (Indicator::Sticky, "37;44"),
+    (Indicator::OtherWritable, "34;42"),
+    (Indicator::StickyOtherWritable, "30;42"),
+    (Indicator::ClearToEol, "\x1b[K"),
+];
+
+/// A `*pattern` entry of LS_COLORS, which colors the files whose names end with the pattern in any case
+struct Suffix {
+    suffix: Vec<u8>,
+    sequence: Vec<u8>,
+}
+
+/// A parsed LS_COLORS, which paints file names with the escape sequences it picks for them
+pub struct LsColors {
+    indicators: HashMap<Indicator, Vec<u8>>,
+    suffixes: Vec<Suffix>,
+    /// ln=target, links take the color of what they point to
+    link_as_target: bool,
+    /// Link targets were looked at anyway, so they can be colored by what they are
+    targets_known: bool,
+    /// Whether a color was used yet, the first one resets the terminal first
+    started: Cell<bool>,
+}
+
+const UNPARSABLE: &str = "unparsable value for LS_COLORS environment variable";
+
+/// A key or value of LS_COLORS, up to the `:` that ends it (or the `=` with `equals_end`) which is left for the
+/// caller. Escapes are resolved like coreutils' get_funky_string does: \e, \033, \x1b, ^[ and the like.
+fn unescape(spec: &mut Peekable<Copied<Iter<u8>>>, equals_end: bool) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    while let Some(b) = spec.next_if(|b| *b != b':' && (*b != b'=' || !equals_end)) {
+        let byte = match b {
+            b'\\' => match spec.next()? {
+                // As many digits as there are, the value wraps around like coreutils' char does
+                digit @ b'0'..=b'7' => {
+                    let mut value = digit - b'0';
+                    while let Some(digit) = spec.next_if(|b| (b'0'..=b'7').contains(b)) {
+                        value = value.wrapping_mul(8).wrapping_add(digit - b'0');
+                    }
+                    value
+                }
+                b'x' | b'X' => {
+                    let mut value: u8 = 0;
+                    while let Some(digit) = spec.next_if(u8::is_ascii_hexdigit) {
+                        // OK because it is a hex digit
+                        value = value.wrapping_mul(16).wrapping_add(char::from(digit).to_digit(16).unwrap() as u8);
+                    }
+                    value
+                }
+                b'a' => 0x07,
+                b'b' => 0x08,
+                b'e' => 0x1b,
+                b'f' => 0x0c,
+                b'n' => b'\n',
+                b'r' => b'\r',
+                b't' => b'\t',
+                b'v' => 0x0b,
+                b'?' => 0x7f,
+                b'_' => b' ',
+                b => b,
+            },
+            b'^' => match spec.next()? {
+                b'?' => 0x7f,
+                b @ b'@'..=b'~' => b & 0o37,
+                _ => return None,
+            },
+            b => b,
+        };
+        out.push(byte);
+    }
+    Some(out)
+}
+
+impl LsColors {
+    /// `spec` on top of the builtin colors
+    pub fn parse(spec: &[u8]) -> Result<LsColors, String> {
+        let mut colors = LsColors {
+            indicators: BUILTIN_INDICATORS.iter().map(|(indicator, sequence)| (*indicator, sequence.as_bytes().to_vec())).collect(),
+            suffixes: Vec::new(),
+            link_as_target: false,
+            targets_known: false,
+            started: Cell::new(false),
+        };
+        let unparsable = || UNPARSABLE.to_string();
+        let mut spec = spec.iter().copied().peekable();
+        while let Some(b) = spec.peek() {
+            if *b == b':' {
+                spec.next();
+                continue;
+            }
+            // Suffixes can have escapes too, indicators are just their two letters
+            let suffix = match spec.next_if_eq(&b'*') {
+                Some(_) => Some(unescape(&mut spec, true).ok_or_else(unparsable)?),
+                None => None,
+            };
+            let key: Vec<u8> = match suffix {
+                Some(_) => Vec::new(),
+                None => spec.by_ref().take(2).collect(),
+            };
+            if spec.next() != Some(b'=') {
+                return Err(unparsable());
+            }
+            let sequence = unescape(&mut spec, false).ok_or_else(unparsable)?;
+            if let Some(suffix) = suffix {
+                colors.suffixes.push(Suffix { suffix, sequence });
+                continue;
+            }
+            let indicator = std::str::from_utf8(&key).ok().and_then(|key| Indicator::from_str(key).ok()).ok_or_else(unparsable)?;
+            if indicator == Indicator::Link && sequence == b"target" {
+                colors.link_as_target = true;
+                colors.indicators.remove(&indicator);
+                continue;
+            }
+            colors.indicators.insert(indicator, sequence);
+        }
+        Ok(colors)
+    }
+
+    /// LS_COLORS, or the default database when it isn't set
+    pub fn from_env() -> Result<LsColors, String> {
+        match env::var_os("LS_COLORS") {
+            Some(spec) => LsColors::parse(spec.as_bytes()),
+            // The default database always compiles
+            None => LsColors::parse(compile_database(DEFAULT_DATABASE, None).unwrap().as_bytes()),
+        }
+    }
+
+    /// Color link targets by what they are even when the colors alone wouldn't need it, like coreutils does
+    /// when it looks at them for the -F indicators
+    pub fn with_targets_known(self) -> LsColors {
+        LsColors { targets_known: true, ..self }
+    }
+
+    /// Whether painting looks at what links point to, only then do they have to be loaded with
+    /// FileMetadata::with_link_mode
+    pub fn needs_link_modes(&self) -> bool {
+        self.checks_links() || self.targets_known
+    }
+
+    /// Like coreutils, what a link points to only matters when it can change the color
+    fn checks_links(&self) -> bool {
+        self.link_as_target || self.is_colored(Indicator::Orphan)
+    }
+
+    fn get(&self, indicator: Indicator) -> Option<&[u8]> {
+        self.indicators.get(&indicator).map(Vec::as_slice)
+    }
+
+    /// Set to something that actually changes the color
+    fn is_colored(&self, indicator: Indicator) -> bool {
+        !matches!(self.get(indicator), None | Some(b"") | Some(b"0") | Some(b"00"))
+    }
+
+    fn indicator_for_mode(&self, mode: u32, nlink: u64) -> Indicator {
+        let has = |bits: PermissionsMask| mode & bits.bits() != 0;
+        match FileType::try_from(mode & 0o170000) {
+            Ok(FileType::RegularFile) => {
+                if has(PermissionsMask::S_ISUID) && self.is_colored(Indicator::SetUid) {
+                    Indicator::SetUid
+                } else if has(PermissionsMask::S_ISGID) && self.is_colored(Indicator::SetGid) {
+                    Indicator::SetGid
+                } else if PermissionsMask::build(mode).is_executable() && self.is_colored(Indicator::Executable) {
+                    Indicator::Executable
+                } else if nlink > 1 && self.is_colored(Indicator::MultiHardLink) {
+                    Indicator::MultiHardLink
+                } else {
+                    Indicator::File
+                }
+            }
+            Ok(FileType::Directory) => {
+                let (sticky, other_writable) = (has(PermissionsMask::S_ISVTX), has(PermissionsMask::S_IWOTH));
+                if sticky && other_writable && self.is_colored(Indicator::StickyOtherWritable) {
+                    Indicator::StickyOtherWritable
+                } else if other_writable && self.is_colored(Indicator::OtherWritable) {
+                    Indicator::OtherWritable
+                } else if sticky && self.is_colored(Indicator::Sticky) {
+                    Indicator::Sticky
+                } else {
+                    Indicator::Directory
+                }
+            }
+            Ok(FileType::SymbolicLink) => Indicator::Link,
+            Ok(FileType::Fifo) => Indicator::Fifo,
+            Ok(FileType::Socket) => Indicator::Socket,
+            Ok(FileType::BlockDevice) => Indicator::BlockDevice,
+            Ok(FileType::CharDevice) => Indicator::CharDevice,
+            Err(_) => Indicator::Orphan,
+        }
+    }
+
+    fn suffix_sequence(&self, name: &[u8]) -> Option<&[u8]> {
+        // Later entries override earlier ones
+        self.suffixes.iter().rev().find(|entry| {
+            name.len() >= entry.suffix.len() && name[name.len() - entry.suffix.len()..].eq_ignore_ascii_case(&entry.suffix)
+        }).map(|entry| entry.sequence.as_slice())
+    }
+
+    /// The sequence for `meta`'s name, or for the target of the link `meta` is
+    fn sequence(&self, meta: &FileMetadata, target: bool) -> Option<&[u8]> {
+        let check_links = self.checks_links();
+        let link_mode = meta.link_mode().filter(|_| self.needs_link_modes());
+        let (name, mode) = if target {
+            if link_mode.is_none() && self.is_colored(Indicator::Missing) {
+                return self.get(Indicator::Missing);
+            }
+            (meta.link_target().map_or(&[][..], |target| target.as_os_str().as_bytes()), link_mode.unwrap_or(0))
+        } else {
+            let mode = link_mode.filter(|_| self.link_as_target).unwrap_or_else(|| meta.mode());
+            (meta.name.as_bytes(), mode)
+        };
+        let mut indicator = self.indicator_for_mode(mode, meta.nlink());
+        if indicator == Indicator::File {
+            if let Some(sequence) = self.suffix_sequence(name) {
+                return Some(sequence);
+            }
+        }
+        if indicator == Indicator::Link && link_mode.is_none() && check_links {
+            indicator = Indicator::Orphan;
+        }
+        self.get(indicator)
+    }
+
+    /// What ends a colored name, and restores the terminal before the first one
+    fn reset(&self) -> Vec<u8> {
+        match self.get(Indicator::End) {
+            Some(end) => end.to_vec(),
+            None => [Indicator::Left, Indicator::Reset, Indicator::Right].iter()
+                .flat_map(|indicator| self.get(*indicator).unwrap_or(b"")).copied().collect(),
+        }
+    }
+
+    /// What goes after the listing once names were colored: like coreutils, lc and rc on their own, unless they are
+    /// the usual \e[ and m that need no restoring
+    pub fn finish(&self) -> Vec<u8> {
+        let (left, right) = (self.get(Indicator::Left).unwrap_or(b""), self.get(Indicator::Right).unwrap_or(b""));
+        if !self.started.get() || (left == b"\x1b[" && right == b"m") {
+            return Vec::new();
+        }
+        [left, right].concat()
+    }
+
+    /// `text` (`meta`'s name, or its link target with `target`) wrapped in the escape sequences of its color
+    pub fn paint<'a>(&self, meta: &FileMetadata, target: bool, text: Cow<'a, [u8]>) -> Cow<'a, [u8]> {
+        let sequence = match self.sequence(meta, target) {
+            Some(sequence) => sequence,
+            None => return text,
+        };
+        let mut painted = Vec::new();
+        if !self.started.replace(true) {
+            painted.extend_from_slice(&self.reset());
+        }
+        painted.extend_from_slice(self.get(Indicator::Left).unwrap_or(b""));
+        painted.extend_from_slice(sequence);
+        painted.extend_from_slice(self.get(Indicator::Right).unwrap_or(b""));
+        painted.extend_from_slice(&text);
+        painted.extend_from_slice(&self.reset());
+        Cow::Owned(painted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LsColors;
+    use std::fs;
+    use std::path::Path;
+    use crate::core::test_support::setup_dir;
+    use crate::librb::file::filemeta::FileMetadata;
+
+    #[test]
+    fn test_paint() {
+        let dir = &setup_dir("test_paint");
+        let dir = Path::new(dir);
+        fs::write(dir.join("a.TAR"), "").unwrap();
+        fs::create_dir(dir.join("d")).unwrap();
+        std::os::unix::fs::symlink("a.TAR", dir.join("link")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.join("dangling")).unwrap();
+        let meta = |name: &str| FileMetadata::for_path(&dir.join(name)).unwrap().with_link_mode(&dir.join(name));
+        let paint = |colors: &LsColors, name: &str, target: bool, text: &str| {
+            String::from_utf8(colors.paint(&meta(name), target, text.as_bytes().into()).into_owned()).unwrap()
+        };
+
+        let colors = LsColors::parse(b"*.tar=31:or=41").unwrap();
+        assert_eq!(paint(&colors, "a.TAR", false, "a.TAR"), "\x1b[0m\x1b[31ma.TAR\x1b[0m");
+        // The reset only comes before the first colored name
+        assert_eq!(paint(&colors, "d", false, "d"), "\x1b[01;34md\x1b[0m");
+        assert_eq!(paint(&colors, "link", false, "link"), "\x1b[01;36mlink\x1b[0m");
+        assert_eq!(paint(&colors, "link", true, "a.TAR"), "\x1b[31ma.TAR\x1b[0m");
+        assert_eq!(paint(&colors, "dangling", false, "dangling"), "\x1b[41mdangling\x1b[0m");
+
+        // Links take the type of what they point to but keep their own name, suffixes match in any case
+        let colors = LsColors::parse(b"ln=target:*.tar=31:*.TAR=32:di=:fi=1").unwrap();
+        assert_eq!(paint(&colors, "link", false, "link"), "\x1b[0m\x1b[1mlink\x1b[0m");
+        assert_eq!(paint(&colors, "link", true, "a.TAR"), "\x1b[32ma.TAR\x1b[0m");
+        assert_eq!(paint(&colors, "a.TAR", false, "a.TAR"), "\x1b[32ma.TAR\x1b[0m");
+        assert_eq!(paint(&colors, "dangling", false, "dangling"), "dangling");
+        assert!(LsColors::parse(b"di=1:bogus").is_err());
+        assert_eq!(LsColors::parse(b"zz=1").err().unwrap(), "unparsable value for LS_COLORS environment variable");
+    }
+
+    #[test]
+    fn test_parse_escapes() {
+        let dir = &setup_dir("test_parse_escapes");
+        let dir = Path::new(dir);
+        fs::write(dir.join("a:b"), "").unwrap();
+        fs::create_dir(dir.join("d")).unwrap();
+        let meta = |name: &str| FileMetadata::for_path(&dir.join(name)).unwrap();
+        let paint = |spec: &[u8], name: &str| {
+            let colors = LsColors::parse(spec).unwrap();
+            colors.paint(&meta(name), false, name.as_bytes().into()).into_owned()
+        };
+
+        for spec in [&br"lc=\e[:rc=m"[..], br"lc=^[[:rc=\x6d", br"lc=\033[:rc=\155", br"lc=\x1B[:rc=\m"].iter() {
+            assert_eq!(paint(spec, "d"), b"\x1b[0m\x1b[01;34md\x1b[0m", "{}", String::from_utf8_lossy(spec));
+        }
+        // Escaped colons and equal signs don't end the suffix, \_ is a space and ^? is DEL
+        assert_eq!(paint(br"*a\:b=3\=1:lc=<:rc=>:ec=\_^?", "a:b"), b" \x7f<3=1>a:b \x7f");
+        for spec in [&br"lc=\"[..], b"lc=^", b"lc=^1", b"*a", b"*a:b=1", b"l"].iter() {
+            assert!(LsColors::parse(spec).is_err(), "{}", String::from_utf8_lossy(spec));
+        }
+    }
+}
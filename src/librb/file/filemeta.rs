@@ -1,5 +1,3 @@
-use chrono::DateTime;
-use chrono::Local;
 use core::convert::TryFrom;
 use core::fmt;
 use core::fmt::Formatter;
@@ -7,55 +5,241 @@ use core::option::Option::{None, Some};
 use core::option::Option;
 use std::os::unix::fs::MetadataExt;
 use std::os::unix::fs::PermissionsExt;
-use core::result::Result::Ok;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::path::Path;
-use std::time::SystemTime;
-use chrono::Duration;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::thread::LocalKey;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use users::{get_user_by_uid, get_group_by_gid};
 use crate::librb::file::filetype::FileType;
 use crate::librb::file::permissions::{PermissionsMask};
+#[cfg(feature = "json")]
+use crate::librb::json;
+use crate::librb::quote::escape_invalid_utf8;
+#[cfg(feature = "json")]
+use crate::librb::quote::escape_reversibly;
+use crate::librb::size::SizeFormat;
+#[cfg(feature = "json")]
+use crate::librb::time::strftime;
+use crate::librb::time::TimeStyle;
 
 pub struct FileMetadata {
-    pub name: String,
+    pub name: OsString,
     permissions: PermissionsMask,
     size: u64,
     file_type: FileType,
     mtime: SystemTime,
+    atime: SystemTime,
+    ctime: SystemTime,
+    btime: Option<SystemTime>,
     uid: Uid,
     gid: Gid,
+    ino: u64,
+    dev: u64,
+    rdev: u64,
+    nlink: u64,
+    blocks: u64,
+    blksize: u64,
+    link_target: Option<PathBuf>,
+    /// st_mode of what a symbolic link points to, None when it dangles
+    link_mode: Option<u32>,
+}
+
+/// Which of its timestamps a file is listed with, the words are the ones of ls --time
+#[derive(EnumString, PartialEq, Clone, Copy, Debug)]
+pub enum TimeField {
+    #[strum(serialize = "mtime", serialize = "modification")]
+    Modification,
+    #[strum(serialize = "atime", serialize = "access", serialize = "use")]
+    Access,
+    #[strum(serialize = "ctime", serialize = "status")]
+    Change,
+    #[strum(serialize = "birth", serialize = "creation")]
+    Birth,
+}
+
+/// The last component of `path`, like /, . or .. when that is all there is
+fn file_name(path: &Path) -> &OsStr {
+    path.components().next_back().map_or(path.as_os_str(), |component| component.as_os_str())
+}
+
+/// st_ctim as a SystemTime, std only exposes it as raw seconds and nanoseconds
+fn change_time(f: &fs::Metadata) -> SystemTime {
+    let nanos = Duration::from_nanos(f.ctime_nsec() as u64);
+    if f.ctime() >= 0 {
+        UNIX_EPOCH + Duration::from_secs(f.ctime() as u64) + nanos
+    } else {
+        UNIX_EPOCH - Duration::from_secs(f.ctime().unsigned_abs()) + nanos
+    }
 }
 
 impl FileMetadata {
     pub fn is_hidden(&self) -> bool {
-        self.name.starts_with(".")
+        self.name.as_bytes().starts_with(b".")
     }
     pub fn for_path(p: &Path) -> Option<FileMetadata> {
-        if let Ok(f) = fs::symlink_metadata(p) {
-            let filename = p.components().last().unwrap().as_os_str().to_str().unwrap().to_string();
-            let name : String = filename;
-            let size = f.len();
-            let uid = Uid { uid: f.uid() };
-            let gid = Gid { gid: f.gid() };
-            let mode = f.permissions().mode();
-            if let Ok(mtime) = f.modified() {
-                if let Ok(file_type) = FileType::try_from(f) {
-                    return Some(FileMetadata {
-                        name,
-                        permissions: PermissionsMask::build(mode),
-                        size,
-                        mtime,
-                        uid,
-                        gid,
-                        file_type,
-                    });
-                }
-            }
+        FileMetadata::load(p, false).ok()
+    }
+    /// stat(2) when `dereference`, lstat(2) otherwise. What a symbolic link points to is left alone, see
+    /// with_link_mode
+    pub fn load(p: &Path, dereference: bool) -> io::Result<FileMetadata> {
+        let f = if dereference { fs::metadata(p)? } else { fs::symlink_metadata(p)? };
+        let file_type = FileType::try_from(f.clone()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        let is_link = file_type == FileType::SymbolicLink;
+        Ok(FileMetadata {
+            name: file_name(p).to_os_string(),
+            permissions: PermissionsMask::build(f.permissions().mode()),
+            size: f.len(),
+            file_type,
+            mtime: f.modified()?,
+            atime: f.accessed()?,
+            ctime: change_time(&f),
+            // statx on Linux, not every file system records it
+            btime: f.created().ok(),
+            uid: Uid { uid: f.uid() },
+            gid: Gid { gid: f.gid() },
+            ino: f.ino(),
+            dev: f.dev(),
+            rdev: f.rdev(),
+            nlink: f.nlink(),
+            blocks: f.blocks(),
+            blksize: f.blksize(),
+            link_target: if is_link { fs::read_link(p).ok() } else { None },
+            link_mode: None,
+        })
+    }
+    /// The file name as printable text, only meant for display
+    pub fn display_name(&self) -> Cow<'_, str> {
+        escape_invalid_utf8(&self.name)
+    }
+
+    /// The record `ls --json` and `stat --json` print for the file at `path`, the fields are described under JSON
+    /// output in README.md
+    #[cfg(feature = "json")]
+    pub fn to_json(&self, path: &Path) -> json::Value {
+        let time = |time: SystemTime| strftime(time, "%Y-%m-%dT%H:%M:%S.%N%:z");
+        json::Value::Object(vec![
+            // The last component whether the file was found in a directory or given as an operand
+            ("name", escape_reversibly(file_name(path)).into()),
+            ("path", escape_reversibly(path.as_os_str()).into()),
+            ("type", self.file_type.name().into()),
+            ("mode", format!("{:04o}", self.permissions).into()),
+            ("permissions", format!("{}{}", self.file_type, self.permissions).into()),
+            ("size", self.size.into()),
+            ("uid", self.uid.uid.into()),
+            ("user", self.uid.get_name().into()),
+            ("gid", self.gid.gid.into()),
+            ("group", self.gid.get_name().into()),
+            ("atime", time(self.atime).into()),
+            ("mtime", time(self.mtime).into()),
+            ("ctime", time(self.ctime).into()),
+            ("btime", self.btime.map(time).into()),
+            ("target", self.link_target.as_ref().map(|target| escape_reversibly(target.as_os_str()).into_owned()).into()),
+        ])
+    }
+}
+
+// The stat information every applet built on FileMetadata picks from
+impl FileMetadata {
+    pub fn permissions(&self) -> PermissionsMask {
+        self.permissions
+    }
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+    /// The raw st_mode, file type and permission bits
+    pub fn mode(&self) -> u32 {
+        self.file_type as u32 | self.permissions.bits()
+    }
+    pub fn is_dir(&self) -> bool {
+        self.file_type == FileType::Directory
+    }
+    pub fn is_device(&self) -> bool {
+        self.file_type == FileType::BlockDevice || self.file_type == FileType::CharDevice
+    }
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+    /// Allocated size in 512 byte units
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+    /// The preferred I/O block size of the file system
+    pub fn blksize(&self) -> u64 {
+        self.blksize
+    }
+    pub fn mtime(&self) -> SystemTime {
+        self.mtime
+    }
+    pub fn atime(&self) -> SystemTime {
+        self.atime
+    }
+    /// Last status change, not creation
+    pub fn ctime(&self) -> SystemTime {
+        self.ctime
+    }
+    /// Creation time, when the file system and kernel report it
+    pub fn btime(&self) -> Option<SystemTime> {
+        self.btime
+    }
+    pub fn time(&self, field: TimeField) -> Option<SystemTime> {
+        match field {
+            TimeField::Modification => Some(self.mtime),
+            TimeField::Access => Some(self.atime),
+            TimeField::Change => Some(self.ctime),
+            TimeField::Birth => self.btime,
         }
-        return None;
     }
-    pub fn short_name(&self) -> &String {
-        &self.name
+    pub fn owner(&self) -> &Uid {
+        &self.uid
+    }
+    pub fn group(&self) -> &Gid {
+        &self.gid
+    }
+    pub fn uid(&self) -> u32 {
+        self.uid.uid
+    }
+    pub fn gid(&self) -> u32 {
+        self.gid.gid
+    }
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+    /// The device the file lives on
+    pub fn dev(&self) -> u64 {
+        self.dev
+    }
+    /// The device a block/char device node stands for
+    pub fn rdev(&self) -> u64 {
+        self.rdev
+    }
+    pub fn rdev_major(&self) -> u32 {
+        libc::major(self.rdev)
+    }
+    pub fn rdev_minor(&self) -> u32 {
+        libc::minor(self.rdev)
+    }
+    pub fn nlink(&self) -> u64 {
+        self.nlink
+    }
+    /// Where a symbolic link points to, None for anything else
+    pub fn link_target(&self) -> Option<&Path> {
+        self.link_target.as_deref()
+    }
+    /// The raw st_mode of what a symbolic link points to, None for anything else, for dangling links and
+    /// unless it was loaded with_link_mode
+    pub fn link_mode(&self) -> Option<u32> {
+        self.link_mode
+    }
+    /// Also stat(2) what the symbolic link at `p` points to, which only colors and indicators of links need
+    pub fn with_link_mode(self, p: &Path) -> FileMetadata {
+        let link_mode = self.link_target.as_ref().and_then(|_| fs::metadata(p).ok()).map(|target| target.mode());
+        FileMetadata { link_mode, ..self }
     }
 }
 
@@ -81,71 +265,223 @@ pub struct Gid{
 
 impl fmt::Display for Uid {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.display_string())
+        f.pad(&self.display_string())
     }
 }
 
 impl fmt::Display for Gid {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.display_string())
-
+        f.pad(&self.display_string())
     }
 }
 
+type NameCache = RefCell<HashMap<u32, Option<String>>>;
+
+thread_local! {
+    /// Names already looked up, a listing asks for the same few ids over and over and each lookup can go to NSS
+    static USER_NAMES: NameCache = RefCell::new(HashMap::new());
+    static GROUP_NAMES: NameCache = RefCell::new(HashMap::new());
+}
+
+/// The name of `id` from `cache`, found with `lookup` the first time
+fn cached_name(cache: &'static LocalKey<NameCache>, id: u32, lookup: impl FnOnce(u32) -> Option<String>) -> Option<String> {
+    cache.with(|names| names.borrow_mut().entry(id).or_insert_with(|| lookup(id)).clone())
+}
+
 impl UidgidDisplay for Uid {
     fn get_name(&self) -> Option<String> {
-        if let Some(s) = get_user_by_uid(self.uid)?.name().to_str() {
-            return Some(s.to_string());
-        }
-        return None;
+        cached_name(&USER_NAMES, self.uid, |uid| get_user_by_uid(uid)?.name().to_str().map(str::to_string))
     }
 
     fn value(&self) -> u32 {
-        return self.uid;
+        self.uid
     }
 }
 impl UidgidDisplay for Gid {
     fn get_name(&self) -> Option<String> {
-        if let Some(s) = get_group_by_gid(self.gid)?.name().to_str() {
-            return Some(s.to_string());
-        }
-        return None;
+        cached_name(&GROUP_NAMES, self.gid, |gid| get_group_by_gid(gid)?.name().to_str().map(str::to_string))
     }
 
     fn value(&self) -> u32 {
-        return self.gid;
+        self.gid
     }
 }
 
-impl fmt::Display for FileMetadata {
+/// How the columns of a long listing are shown
+pub struct LongOptions {
+    pub sizes: SizeFormat,
+    pub times: TimeStyle,
+    pub time: TimeField,
+    pub show_owner: bool,
+    pub show_group: bool,
+    /// The author column, which is the owner on Linux
+    pub show_author: bool,
+    /// Owners and groups as ids even when they have names
+    pub numeric_ids: bool,
+}
+
+impl Default for LongOptions {
+    fn default() -> LongOptions {
+        LongOptions {
+            sizes: SizeFormat::BYTES, times: TimeStyle::LOCALE, time: TimeField::Modification, show_owner: true, show_group: true,
+            show_author: false, numeric_ids: false,
+        }
+    }
+}
+
+/// Lays out `ls -l` lines the way coreutils does, with every column wide enough for all the
+/// entries of the listing
+pub struct LongFormat {
+    now: SystemTime,
+    sizes: SizeFormat,
+    times: TimeStyle,
+    time: TimeField,
+    /// Of the times, for the ? of files without the chosen one
+    time_width: usize,
+    show_owner: bool,
+    show_group: bool,
+    show_author: bool,
+    numeric_ids: bool,
+    nlink_width: usize,
+    owner_width: usize,
+    group_width: usize,
+    size_width: usize,
+    major_width: usize,
+    minor_width: usize,
+}
+
+impl LongFormat {
+    pub fn for_entries<'a>(entries: impl IntoIterator<Item = &'a FileMetadata>) -> LongFormat {
+        LongFormat::with_options(entries, &LongOptions::default())
+    }
+
+    pub fn with_options<'a>(entries: impl IntoIterator<Item = &'a FileMetadata>, options: &LongOptions) -> LongFormat {
+        let (now, sizes) = (SystemTime::now(), options.sizes);
+        let mut fmt = LongFormat {
+            now, sizes, times: options.times.clone(), time: options.time, time_width: options.times.format(UNIX_EPOCH, now).chars().count(),
+            show_owner: options.show_owner, show_group: options.show_group, show_author: options.show_author, numeric_ids: options.numeric_ids,
+            nlink_width: 0, owner_width: 0, group_width: 0, size_width: 0, major_width: 0, minor_width: 0
+        };
+        for meta in entries {
+            fmt.nlink_width = fmt.nlink_width.max(meta.nlink.to_string().len());
+            // Names are only looked up for the columns that show them
+            if fmt.show_owner || fmt.show_author {
+                fmt.owner_width = fmt.owner_width.max(fmt.id_text(&meta.uid).chars().count());
+            }
+            if fmt.show_group {
+                fmt.group_width = fmt.group_width.max(fmt.id_text(&meta.gid).chars().count());
+            }
+            if meta.is_device() {
+                fmt.major_width = fmt.major_width.max(meta.rdev_major().to_string().len());
+                fmt.minor_width = fmt.minor_width.max(meta.rdev_minor().to_string().len());
+            } else {
+                fmt.size_width = fmt.size_width.max(sizes.format(meta.size, 1).len());
+            }
+        }
+        if fmt.major_width > 0 {
+            fmt.size_width = fmt.size_width.max(fmt.major_width + 2 + fmt.minor_width);
+        }
+        fmt
+    }
+
+    /// What the owner or group column shows for `id`
+    fn id_text(&self, id: &dyn UidgidDisplay) -> String {
+        self.name_of(id).unwrap_or_else(|| id.value().to_string())
+    }
+
+    fn name_of(&self, id: &dyn UidgidDisplay) -> Option<String> {
+        if self.numeric_ids { None } else { id.get_name() }
+    }
+
+    /// Like coreutils, names are aligned left and ids without one right
+    fn write_id(&self, f: &mut Formatter<'_>, id: &dyn UidgidDisplay, width: usize) -> fmt::Result {
+        match self.name_of(id) {
+            Some(name) => write!(f, "{:<width$} ", name, width=width),
+            None => write!(f, "{:>width$} ", id.value(), width=width),
+        }
+    }
+
+    pub fn display<'a>(&'a self, meta: &'a FileMetadata) -> LongEntry<'a> {
+        let target = meta.link_target.as_ref().map(|target| escape_invalid_utf8(target.as_os_str()));
+        LongEntry { fmt: self, meta, name: Some(meta.display_name()), target }
+    }
+
+    /// Like `display`, stopping before the name so the caller can write it as it likes, e.g. colored or as raw bytes
+    pub fn display_columns<'a>(&'a self, meta: &'a FileMetadata) -> LongEntry<'a> {
+        LongEntry { fmt: self, meta, name: None, target: None }
+    }
+}
+
+/// A single line of a long listing
+pub struct LongEntry<'a> {
+    fmt: &'a LongFormat,
+    meta: &'a FileMetadata,
+    name: Option<Cow<'a, str>>,
+    target: Option<Cow<'a, str>>,
+}
+
+impl fmt::Display for LongEntry<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.file_type)?;
-        write!(f, "{} ", self.permissions)?;
-        write!(f, "{:<8} ", self.size)?;
-        write!(f, "{:<8} ", self.uid)?;
-        write!(f, "{:<8} ", self.gid)?;
-
-        let date: DateTime<Local> = self.mtime.into();
-        // TODO: Move to a seperate type
-        if (date - Local::now()) > Duration::seconds( 24 * 60 * 60 ) {
-            write!(f, "{:<8} ", date.format("%d %b %Y"))?;
+        let (fmt, meta) = (self.fmt, self.meta);
+        write!(f, "{}{} ", meta.file_type, meta.permissions)?;
+        write!(f, "{:>width$} ", meta.nlink, width=fmt.nlink_width)?;
+        if fmt.show_owner {
+            fmt.write_id(f, &meta.uid, fmt.owner_width)?;
+        }
+        if fmt.show_group {
+            fmt.write_id(f, &meta.gid, fmt.group_width)?;
+        }
+        if fmt.show_author {
+            fmt.write_id(f, &meta.uid, fmt.owner_width)?;
+        }
+        if meta.is_device() {
+            // Device nodes show which device they are instead of a size
+            let device = format!("{:>major$}, {:>minor$}", meta.rdev_major(), meta.rdev_minor(),
+                                 major=fmt.major_width, minor=fmt.minor_width);
+            write!(f, "{:>width$} ", device, width=fmt.size_width)?;
         } else {
-            write!(f, "{:<8} ", date.format("%d %b %H:%M"))?;
+            write!(f, "{:>width$} ", fmt.sizes.format(meta.size, 1), width=fmt.size_width)?;
+        }
+
+        match meta.time(fmt.time) {
+            Some(time) => write!(f, "{} ", fmt.times.format(time, fmt.now))?,
+            None => write!(f, "{:>width$} ", "?", width=fmt.time_width)?,
+        }
+        if let Some(name) = &self.name {
+            write!(f, "{}", name)?;
         }
-        write!(f, "{}", self.name)
+        if let Some(target) = &self.target {
+            write!(f, " -> {}", target)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FileMetadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", LongFormat::for_entries(std::iter::once(self)).display(self))
     }
 }
 
 #[cfg(test)]
 pub mod tests {
+    use std::ffi::OsStr;
+    use std::fs;
     use std::io;
-    use std::path::PathBuf;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
     use std::process::{Command, Output};
+    use std::time::UNIX_EPOCH;
 
     use users::{get_current_uid, get_current_gid};
     use super::FileType;
     use super::PermissionsMask;
     use super::FileMetadata;
+    use super::{cached_name, NameCache};
+    use crate::core::test_support::setup_dir;
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
 
     pub struct TestCaseData {
         path: String,
@@ -181,7 +517,7 @@ pub mod tests {
     #[test]
     fn test_current_file_metadata() -> Result<(), io::Error>{
         let case = setup_test();
-        let meta = FileMetadata::for_path(&PathBuf::from(case.path));
+        let meta = FileMetadata::for_path(&PathBuf::from(&case.path));
         match meta {
             Some(meta) => {
                 assert_eq!(meta.file_type, FileType::RegularFile);
@@ -195,11 +531,108 @@ pub mod tests {
                 assert_eq!(meta.uid.uid, case.uid);
                 assert_eq!(meta.gid.gid, case.gid);
                 assert_eq!(meta.size, case.size);
-                assert_eq!(meta.name, case.name);
+                assert_eq!(meta.name, case.name.as_str());
             }
-            None => assert!(false)
+            None => panic!("no metadata for {}", case.path)
         }
         Ok(())
     }
-}
 
+    #[test]
+    fn test_non_utf8_file_name() {
+        let dir = &setup_dir("test_non_utf8_file_name");
+        let dir = Path::new(dir);
+        for (name, display) in [(&b"caf\xe9"[..], "caf\\xe9"), (&b".\xff"[..], ".\\xff")].iter() {
+            let path = dir.join(OsStr::from_bytes(name));
+            fs::write(&path, "").unwrap();
+            let meta = FileMetadata::for_path(&path).unwrap();
+            assert_eq!(meta.name.as_bytes(), *name);
+            assert_eq!(meta.is_hidden(), name[0] == b'.');
+            assert_eq!(meta.display_name(), *display);
+            assert!(format!("{}", meta).ends_with(display));
+        }
+    }
+
+    #[test]
+    fn test_stat_fields() {
+        let case = setup_test();
+        let meta = FileMetadata::for_path(Path::new(&case.path)).unwrap();
+        let expected = fs::symlink_metadata(&case.path).unwrap();
+        assert_eq!(meta.ino(), expected.ino());
+        assert_eq!(meta.dev(), expected.dev());
+        assert_eq!(meta.nlink(), 1);
+        assert_eq!(meta.blocks(), expected.blocks());
+        assert_eq!(meta.blksize(), expected.blksize());
+        assert_eq!(meta.atime(), expected.accessed().unwrap());
+        assert_eq!(meta.ctime().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64, expected.ctime());
+        assert_eq!(meta.btime(), expected.created().ok());
+        assert_eq!((meta.uid(), meta.gid()), (case.uid, case.gid));
+        assert!(meta.link_target().is_none());
+        assert!(!meta.is_device());
+    }
+
+    #[test]
+    fn test_device_and_link() {
+        let null = FileMetadata::for_path(Path::new("/dev/null")).unwrap();
+        assert_eq!(null.file_type, FileType::CharDevice);
+        assert_eq!((null.rdev_major(), null.rdev_minor()), (1, 3));
+        assert!(format!("{}", null).contains(" 1, 3 "));
+
+        let dir = &setup_dir("test_device_and_link");
+        let dir = Path::new(dir);
+        std::os::unix::fs::symlink("/dev/null", dir.join("link")).unwrap();
+        let link = FileMetadata::for_path(&dir.join("link")).unwrap();
+        assert_eq!(link.file_type, FileType::SymbolicLink);
+        assert_eq!(link.link_target(), Some(Path::new("/dev/null")));
+        assert!(format!("{}", link).ends_with("link -> /dev/null"));
+        std::os::unix::fs::symlink("missing", dir.join("dangling")).unwrap();
+        // Targets are only looked at on request
+        assert_eq!(link.link_mode(), None);
+        assert_eq!(link.with_link_mode(&dir.join("link")).link_mode(), Some(null.mode()));
+        let dangling = dir.join("dangling");
+        assert_eq!(FileMetadata::for_path(&dangling).unwrap().with_link_mode(&dangling).link_mode(), None);
+    }
+
+    #[test]
+    fn test_cached_name() {
+        thread_local! {
+            static NAMES: NameCache = RefCell::new(HashMap::new());
+        }
+        let lookups = Cell::new(0);
+        let lookup = |id: u32| {
+            lookups.set(lookups.get() + 1);
+            Some(id.to_string()).filter(|_| id != 7)
+        };
+        for id in [1, 7, 1, 7, 2].iter() {
+            assert_eq!(cached_name(&NAMES, *id, lookup), Some(id.to_string()).filter(|_| *id != 7));
+        }
+        // Ids without a name are remembered too
+        assert_eq!(lookups.get(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_to_json() {
+        let dir = &setup_dir("test_to_json");
+        let dir = Path::new(dir);
+        fs::write(dir.join("f"), "hello").unwrap();
+        let run_cmd = |cmd: &str| Command::new("sh").arg("-c").arg(cmd).output().expect("failed to execute process");
+        run_cmd(&format!("chmod 4751 {dir}/f; touch -d '2001-02-03 04:05:06.5' {dir}/f; ln -s f {dir}/l", dir=dir.display()));
+        let json = FileMetadata::for_path(&dir.join("f")).unwrap().to_json(&dir.join("f")).to_string();
+        let date = String::from_utf8(run_cmd("date -d '2001-02-03 04:05:06.5' +%Y-%m-%dT%H:%M:%S.%N%:z").stdout).unwrap();
+        let names = format!(r#""uid":{uid},"user":"{user}","gid":{gid},"group":"{group}""#, uid=get_current_uid(), gid=get_current_gid(),
+                            user=users::get_current_username().unwrap().to_str().unwrap(),
+                            group=users::get_current_groupname().unwrap().to_str().unwrap());
+        assert!(json.starts_with(&format!(r#"{{"name":"f","path":"{}/f","type":"file","mode":"4751","permissions":"-rwsr-x--x","size":5,{},"atime":"#,
+                                          dir.display(), names)), "{}", json);
+        assert!(json.contains(&format!(r#""mtime":"{}""#, date.trim())), "{}", json);
+        assert!(json.ends_with(r#""target":null}"#));
+        let link = FileMetadata::for_path(&dir.join("l")).unwrap().to_json(Path::new("l")).to_string();
+        assert!(link.contains(r#""path":"l","type":"symlink","#) && link.ends_with(r#""target":"f"}"#), "{}", link);
+        // A backslash in a name is told apart from a byte that isn't UTF-8
+        let odd = dir.join(OsStr::from_bytes(b"a\\xff\xff"));
+        fs::write(&odd, "").unwrap();
+        let json = FileMetadata::for_path(&odd).unwrap().to_json(&odd).to_string();
+        assert!(json.starts_with(&format!(r#"{{"name":"a\\\\xff\\xff","path":"{}/a\\\\xff\\xff","#, dir.display())), "{}", json);
+    }
+}
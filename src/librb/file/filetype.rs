@@ -20,10 +20,37 @@ pub enum FileType {
     Directory       = 0o040000,
     #[strum(serialize="c")]
     CharDevice      = 0o020000 ,
-    #[strum(serialize="f")]
+    #[strum(serialize="p")]
     Fifo            = 0o010000,
 }
 
+impl FileType {
+    /// A name that stays the same across releases, for output that programs read
+    pub fn name(self) -> &'static str {
+        match self {
+            FileType::Socket => "socket",
+            FileType::SymbolicLink => "symlink",
+            FileType::RegularFile => "file",
+            FileType::BlockDevice => "block-device",
+            FileType::Directory => "directory",
+            FileType::CharDevice => "char-device",
+            FileType::Fifo => "fifo",
+        }
+    }
+
+    /// The character ls -F puts after names of this type, * is for regular files that are `executable`
+    pub fn indicator(self, executable: bool) -> Option<char> {
+        match self {
+            FileType::Directory => Some('/'),
+            FileType::SymbolicLink => Some('@'),
+            FileType::Fifo => Some('|'),
+            FileType::Socket => Some('='),
+            FileType::RegularFile if executable => Some('*'),
+            _ => None,
+        }
+    }
+}
+
 impl TryFrom<Metadata> for FileType {
     type Error = TryFromPrimitiveError<Self>;
 
@@ -46,4 +73,12 @@ mod tests {
             assert_eq!(x, f);
         }
     }
+
+    #[test]
+    fn test_indicator() {
+        assert_eq!(FileType::Directory.indicator(true), Some('/'));
+        assert_eq!(FileType::RegularFile.indicator(true), Some('*'));
+        assert_eq!(FileType::RegularFile.indicator(false), None);
+        assert_eq!(FileType::CharDevice.indicator(true), None);
+    }
 }
\ No newline at end of file
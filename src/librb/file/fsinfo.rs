@@ -0,0 +1,109 @@
+use std::ffi::CString;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// statfs(2) of the file system a path lives on
+pub struct FsInfo {
+    /// The file system magic number, see statfs(2)
+    pub fs_type: u64,
+    /// The optimal transfer block size
+    pub block_size: u64,
+    /// The unit `blocks`, `blocks_free` and `blocks_available` are counted in
+    pub fragment_size: u64,
+    pub blocks: u64,
+    pub blocks_free: u64,
+    /// Free blocks unprivileged users may use
+    pub blocks_available: u64,
+    pub files: u64,
+    pub files_free: u64,
+    pub fsid: u64,
+    /// Longest file name the file system allows
+    pub name_max: u64,
+}
+
+/// Names coreutils gives the common file system magic numbers
+const FS_TYPE_NAMES: &[(u64, &str)] = &[
+    (0xEF53, "ext2/ext3"),
+    (0x01021994, "tmpfs"),
+    (0x9FA0, "proc"),
+    (0x62656572, "sysfs"),
+    (0x1CD1, "devpts"),
+    (0x794C7630, "overlayfs"),
+    (0x58465342, "xfs"),
+    (0x9123683E, "btrfs"),
+    (0x2FC12FC1, "zfs"),
+    (0x6969, "nfs"),
+    (0x73717368, "squashfs"),
+    (0x4D44, "msdos"),
+    (0x5346544E, "ntfs"),
+    (0x9660, "isofs"),
+    (0x858458F6, "ramfs"),
+    (0x958458F6, "hugetlbfs"),
+    (0x65735546, "fuseblk"),
+    (0x65735543, "fusectl"),
+    (0x01021997, "v9fs"),
+    (0x63677270, "cgroup2fs"),
+    (0x27E0EB, "cgroupfs"),
+    (0x19800202, "mqueue"),
+    (0x64626720, "debugfs"),
+    (0x74726163, "tracefs"),
+    (0x73636673, "securityfs"),
+    (0x6E736673, "nsfs"),
+    (0xCAFE4A11, "bpf_fs"),
+    (0x42494E4D, "binfmt_misc"),
+];
+
+impl FsInfo {
+    pub fn for_path(p: &Path) -> io::Result<FsInfo> {
+        let path = CString::new(p.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut buf = MaybeUninit::<libc::statfs>::uninit();
+        // statfs only reads the path and fills buf when it succeeds
+        let buf = unsafe {
+            if libc::statfs(path.as_ptr(), buf.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            buf.assume_init()
+        };
+        // fsid_t keeps its two words private, coreutils shows them as one number, first word high
+        let fsid: [u32; 2] = unsafe { std::mem::transmute(buf.f_fsid) };
+        Ok(FsInfo {
+            fs_type: buf.f_type as u64,
+            block_size: buf.f_bsize as u64,
+            fragment_size: buf.f_frsize as u64,
+            blocks: buf.f_blocks,
+            blocks_free: buf.f_bfree,
+            blocks_available: buf.f_bavail,
+            files: buf.f_files,
+            files_free: buf.f_ffree,
+            fsid: (fsid[0] as u64) << 32 | fsid[1] as u64,
+            name_max: buf.f_namelen as u64,
+        })
+    }
+
+    /// e.g. "tmpfs", or "UNKNOWN (0x1234)" for a file system we don't know
+    pub fn type_name(&self) -> String {
+        match FS_TYPE_NAMES.iter().find(|(magic, _)| *magic == self.fs_type) {
+            Some((_, name)) => name.to_string(),
+            None => format!("UNKNOWN (0x{:x})", self.fs_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FsInfo;
+    use std::path::Path;
+
+    #[test]
+    fn test_fs_info() {
+        let proc = FsInfo::for_path(Path::new("/proc")).unwrap();
+        assert_eq!(proc.fs_type, 0x9FA0);
+        assert_eq!(proc.type_name(), "proc");
+        let root = FsInfo::for_path(Path::new("/")).unwrap();
+        assert!(root.blocks_free <= root.blocks);
+        assert!(root.name_max > 0);
+        assert!(FsInfo::for_path(Path::new("/rustybox-not-a-path")).is_err());
+    }
+}
@@ -1,3 +1,16 @@
+#[cfg(feature = "permissions")]
 pub mod permissions;
+#[cfg(feature = "mode")]
+pub mod mode;
+#[cfg(feature = "filemeta")]
 pub mod filemeta;
-pub mod filetype;
\ No newline at end of file
+#[cfg(feature = "filemeta")]
+pub mod filetype;
+#[cfg(feature = "sort")]
+pub mod sort;
+#[cfg(feature = "colordb")]
+pub mod colordb;
+#[cfg(feature = "colors")]
+pub mod colors;
+#[cfg(feature = "fsinfo")]
+pub mod fsinfo;
@@ -0,0 +1,265 @@
+use crate::librb::file::permissions::PermissionsMask;
+
+const ALL_BITS: u32 = 0o7777;
+const USER_BITS: u32 = 0o4700;
+const GROUP_BITS: u32 = 0o2070;
+const OTHER_BITS: u32 = 0o1007;
+
+/// The process umask, umask(2) can only be read by setting it so it is immediately restored
+pub fn current_umask() -> u32 {
+    unsafe {
+        let mask = libc::umask(0o022);
+        libc::umask(mask);
+        mask as u32 & 0o777
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ModeOp {
+    Add,
+    Remove,
+    Set,
+}
+
+#[derive(Debug, PartialEq)]
+enum ModePerms {
+    /// rwxst, `conditional_x` is X: execute only for directories or already executable files
+    Bits { bits: u32, conditional_x: bool },
+    /// Octal digits, which name every bit whether they are set or not. Like coreutils they are only taken without
+    /// a who list and at the end of a clause, `=644` but not `o+0`.
+    Octal(u32),
+    /// u, g or o: the current permissions of that class, given as its shift in the mode
+    Copy(u32),
+}
+
+#[derive(Debug, PartialEq)]
+struct ModeAction {
+    op: ModeOp,
+    perms: ModePerms,
+}
+
+/// `[ugoa]*` followed by one or more actions, no who at all means "a, minus the umask"
+#[derive(Debug, PartialEq)]
+pub struct ModeClause {
+    who: Option<u32>,
+    actions: Vec<ModeAction>,
+}
+
+/// A chmod style mode, `755` or `u+x,g-w,o=r`, see chmod(1)
+#[derive(Debug, PartialEq)]
+pub enum ModeExpr {
+    /// Up to 4 digits leave the set-user/group-ID bits of directories alone, like coreutils
+    Octal { mode: u32, keeps_dir_bits: bool },
+    Symbolic(Vec<ModeClause>),
+}
+
+fn parse_octal(s: &str) -> Option<u32> {
+    if s.is_empty() || !s.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+        return None;
+    }
+    u32::from_str_radix(s, 8).ok().filter(|mode| *mode <= ALL_BITS)
+}
+
+fn parse_clause(clause: &str) -> Option<ModeClause> {
+    let mut chars = clause.chars().peekable();
+    let mut who = None;
+    while let Some(c) = chars.peek() {
+        let bits = match c {
+            'u' => USER_BITS,
+            'g' => GROUP_BITS,
+            'o' => OTHER_BITS,
+            'a' => ALL_BITS,
+            _ => break,
+        };
+        who = Some(who.unwrap_or(0) | bits);
+        chars.next();
+    }
+
+    let mut actions = Vec::new();
+    while let Some(c) = chars.next() {
+        let op = match c {
+            '+' => ModeOp::Add,
+            '-' => ModeOp::Remove,
+            '=' => ModeOp::Set,
+            _ => return None,
+        };
+        let perms = match chars.peek() {
+            Some('u') => ModePerms::Copy(6),
+            Some('g') => ModePerms::Copy(3),
+            Some('o') => ModePerms::Copy(0),
+            Some(c) if c.is_ascii_digit() => {
+                let digits: String = std::iter::from_fn(|| chars.next_if(char::is_ascii_digit)).collect();
+                if who.is_some() || chars.peek().is_some() {
+                    return None;
+                }
+                actions.push(ModeAction { op, perms: ModePerms::Octal(parse_octal(&digits)?) });
+                continue;
+            }
+            _ => {
+                let (mut bits, mut conditional_x) = (0, false);
+                while let Some(c) = chars.peek() {
+                    match c {
+                        'r' => bits |= 0o444,
+                        'w' => bits |= 0o222,
+                        'x' => bits |= 0o111,
+                        'X' => conditional_x = true,
+                        's' => bits |= 0o6000,
+                        't' => bits |= 0o1000,
+                        _ => break,
+                    }
+                    chars.next();
+                }
+                actions.push(ModeAction { op, perms: ModePerms::Bits { bits, conditional_x } });
+                continue;
+            }
+        };
+        chars.next();
+        actions.push(ModeAction { op, perms });
+    }
+    if actions.is_empty() {
+        return None;
+    }
+    Some(ModeClause { who, actions })
+}
+
+impl ModeExpr {
+    pub fn parse(s: &str) -> Result<ModeExpr, String> {
+        let invalid = || format!("invalid mode: '{}'", s);
+        if let Some(mode) = parse_octal(s) {
+            return Ok(ModeExpr::Octal { mode, keeps_dir_bits: s.len() < 5 });
+        }
+        if s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        s.split(',').map(parse_clause).collect::<Option<Vec<_>>>().map(ModeExpr::Symbolic).ok_or_else(invalid)
+    }
+
+    /// The mode `mode` becomes, `umask` only limits clauses that don't say who they apply to, octal digits name
+    /// every bit so it doesn't limit them
+    pub fn apply(&self, mode: PermissionsMask, is_dir: bool, umask: u32) -> PermissionsMask {
+        let clauses = match self {
+            ModeExpr::Octal { mode: new, keeps_dir_bits } => {
+                let kept = if is_dir && *keeps_dir_bits { mode.bits() & 0o6000 } else { 0 };
+                return PermissionsMask::build(new | kept);
+            }
+            ModeExpr::Symbolic(clauses) => clauses,
+        };
+        let mut bits = mode.bits();
+        for clause in clauses {
+            let affected = clause.who.unwrap_or(ALL_BITS);
+            for action in &clause.actions {
+                let allowed = match (clause.who, &action.perms) {
+                    (None, ModePerms::Octal(_)) | (Some(_), _) => affected,
+                    (None, _) => affected & !umask,
+                };
+                let value = match action.perms {
+                    ModePerms::Bits { bits: perms, conditional_x } => {
+                        let x = conditional_x && (is_dir || bits & 0o111 != 0);
+                        perms | if x { 0o111 } else { 0 }
+                    }
+                    ModePerms::Octal(perms) => perms,
+                    ModePerms::Copy(shift) => ((bits >> shift) & 0o7) * 0o111,
+                } & allowed;
+                bits = match action.op {
+                    ModeOp::Add => bits | value,
+                    ModeOp::Remove => bits & !value,
+                    ModeOp::Set => {
+                        // Like coreutils, a directory keeps its set-user/group-ID bits unless the clause names them
+                        let named = match action.perms {
+                            ModePerms::Bits { bits: perms, .. } => perms & affected,
+                            ModePerms::Octal(_) => ALL_BITS,
+                            ModePerms::Copy(_) => 0,
+                        };
+                        let kept = if is_dir { 0o6000 & !named } else { 0 };
+                        (bits & !(affected & !kept)) | value
+                    }
+                };
+            }
+        }
+        PermissionsMask::build(bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModeExpr;
+    use crate::librb::file::permissions::PermissionsMask;
+
+    fn chmod(expr: &str, mode: u32, is_dir: bool, umask: u32) -> u32 {
+        ModeExpr::parse(expr).unwrap().apply(PermissionsMask::build(mode), is_dir, umask).bits()
+    }
+
+    #[test]
+    fn test_mode_octal() {
+        assert_eq!(chmod("755", 0o644, false, 0o022), 0o755);
+        assert_eq!(chmod("0", 0o777, false, 0), 0);
+        assert_eq!(chmod("4711", 0o644, false, 0), 0o4711);
+        assert_eq!(chmod("7777", 0, false, 0), 0o7777);
+        // Directories keep their set-ID bits unless 5 digits are given
+        assert_eq!(chmod("755", 0o2700, true, 0), 0o2755);
+        assert_eq!(chmod("00755", 0o2700, true, 0), 0o755);
+        assert_eq!(chmod("755", 0o2700, false, 0), 0o755);
+    }
+
+    #[test]
+    fn test_mode_symbolic() {
+        assert_eq!(chmod("u+x,g-w,o=r", 0o666, false, 0), 0o744);
+        assert_eq!(chmod("a-w", 0o666, false, 0), 0o444);
+        assert_eq!(chmod("ug=rw,o=", 0o777, false, 0), 0o660);
+        assert_eq!(chmod("u=rwx,go=rx", 0, false, 0), 0o755);
+        assert_eq!(chmod("u+r-w", 0o200, false, 0), 0o400);
+        assert_eq!(chmod("=", 0o777, false, 0), 0);
+        assert_eq!(chmod("u=g", 0o750, false, 0), 0o550);
+        assert_eq!(chmod("go=u-w", 0o700, false, 0), 0o755);
+        assert_eq!(chmod("=644", 0o777, false, 0), 0o644);
+        assert_eq!(chmod("-0", 0o640, false, 0), 0o640);
+    }
+
+    #[test]
+    fn test_mode_conditional_x() {
+        assert_eq!(chmod("a+X", 0o644, false, 0), 0o644);
+        assert_eq!(chmod("a+X", 0o744, false, 0), 0o755);
+        assert_eq!(chmod("a+X", 0o644, true, 0), 0o755);
+        assert_eq!(chmod("a-x,a+X", 0o755, false, 0), 0o644);
+    }
+
+    #[test]
+    fn test_mode_special_bits() {
+        assert_eq!(chmod("+t", 0o777, true, 0o022), 0o1777);
+        assert_eq!(chmod("u+s", 0o755, false, 0), 0o4755);
+        assert_eq!(chmod("g+s", 0o755, true, 0), 0o2755);
+        assert_eq!(chmod("o+s", 0o755, false, 0), 0o755);
+        assert_eq!(chmod("+s", 0o755, false, 0), 0o6755);
+        assert_eq!(chmod("u=rw", 0o4755, false, 0), 0o655);
+        assert_eq!(chmod("a-st", 0o7777, false, 0), 0o777);
+        // = leaves the set-ID bits of directories alone unless it names them
+        assert_eq!(chmod("=rwx", 0o2755, true, 0o022), 0o2755);
+        assert_eq!(chmod("=rwx", 0o2755, false, 0o022), 0o755);
+        assert_eq!(chmod("g=u", 0o6755, true, 0), 0o6775);
+        assert_eq!(chmod("a=", 0o6755, true, 0), 0o6000);
+        assert_eq!(chmod("=s", 0o6755, true, 0), 0o6000);
+        assert_eq!(chmod("g=rx", 0o6755, true, 0), 0o6755);
+        assert_eq!(chmod("g=x", 0o6755, true, 0), 0o6715);
+        assert_eq!(chmod("=t", 0o6755, true, 0), 0o7000);
+        assert_eq!(chmod("=755", 0o6755, true, 0), 0o755);
+    }
+
+    #[test]
+    fn test_mode_umask() {
+        assert_eq!(chmod("+w", 0o444, false, 0o022), 0o644);
+        assert_eq!(chmod("a+w", 0o444, false, 0o022), 0o666);
+        assert_eq!(chmod("=rwx", 0o000, false, 0o027), 0o750);
+        assert_eq!(chmod("-w", 0o666, false, 0o022), 0o444 | 0o022);
+        // Octal digits aren't limited by the umask, the clauses after them are
+        assert_eq!(chmod("=644", 0o777, false, 0o077), 0o644);
+        assert_eq!(chmod("=644,+x", 0o600, false, 0o077), 0o744);
+    }
+
+    #[test]
+    fn test_mode_invalid() {
+        for expr in ["", "8", "17777", "u", "u+z", "x+r", "u+r,", "ug", "+rwq", "rw", "755a", "=99",
+                     "o+0", "u=644", "a=755", "=644+x", "=644r", "+12345"].iter() {
+            assert_eq!(ModeExpr::parse(expr), Err(format!("invalid mode: '{}'", expr)), "{}", expr);
+        }
+    }
+}
@@ -10,7 +10,11 @@ bitflags! {
 }
 
 bitflags! {
+    /// The permission bits of a mode, `{}` renders them like ls (rwsr-xr-x), `{:04o}` like stat (4755)
     pub struct PermissionsMask: u32 {
+        const S_ISUID = 0o4000;
+        const S_ISGID = 0o2000;
+        const S_ISVTX = 0o1000;
         const S_IRUSR = 0o0400;
         const S_IWUSR = 0o0200;
         const S_IXUSR = 0o0100;
@@ -33,7 +37,22 @@ impl PermissionsMask {
     pub fn other(&self) -> FilePermissions { self._get_bits(0) }
 
     pub fn build(v: u32) -> Self {
-        Self { bits: v & 0o777 }
+        Self { bits: v & 0o7777 }
+    }
+
+    /// Whether anyone may execute it
+    pub fn is_executable(&self) -> bool {
+        self.intersects(PermissionsMask::S_IXUSR | PermissionsMask::S_IXGRP | PermissionsMask::S_IXOTH)
+    }
+
+    /// rwx for `perms`, with the execute slot showing `special` like ls: s/t when also executable, S/T when not
+    fn _format_group(&self, perms: FilePermissions, special: PermissionsMask, special_char: char) -> String {
+        let mut formatted = format!("{}", perms);
+        if self.contains(special) {
+            let exec = formatted.pop();
+            formatted.push(if exec == Some('x') { special_char } else { special_char.to_ascii_uppercase() });
+        }
+        formatted
     }
 }
 
@@ -47,9 +66,9 @@ impl fmt::Display for FilePermissions {
 
 impl fmt::Display for PermissionsMask{
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.user())?;
-        write!(f, "{}", self.group())?;
-        write!(f, "{}", self.other())
+        write!(f, "{}", self._format_group(self.user(), PermissionsMask::S_ISUID, 's'))?;
+        write!(f, "{}", self._format_group(self.group(), PermissionsMask::S_ISGID, 's'))?;
+        write!(f, "{}", self._format_group(self.other(), PermissionsMask::S_ISVTX, 't'))
     }
 }
 
@@ -81,6 +100,7 @@ mod tests {
         for i in 0..0o1000 {
             let mask = PermissionsMask::build(i);
             assert_eq!(mask.bits, (mask.user().bits << 6 | mask.group().bits << 3 | mask.other().bits));
+            assert_eq!(mask.is_executable(), i & 0o111 != 0);
         }
     }
 
@@ -91,4 +111,25 @@ mod tests {
             assert_eq!(format!("{}", mask), format!("{}{}{}", mask.user(), mask.group(), mask.other()));
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_special_bits_format() {
+        assert_eq!(format!("{}", PermissionsMask::build(0o4755)), "rwsr-xr-x");
+        assert_eq!(format!("{}", PermissionsMask::build(0o4644)), "rwSr--r--");
+        assert_eq!(format!("{}", PermissionsMask::build(0o2755)), "rwxr-sr-x");
+        assert_eq!(format!("{}", PermissionsMask::build(0o2745)), "rwxr-Sr-x");
+        assert_eq!(format!("{}", PermissionsMask::build(0o1777)), "rwxrwxrwt");
+        assert_eq!(format!("{}", PermissionsMask::build(0o1776)), "rwxrwxrwT");
+        assert_eq!(format!("{}", PermissionsMask::build(0o7000)), "--S--S--T");
+        assert_eq!(format!("{}", PermissionsMask::build(0o7777)), "rwsrwsrwt");
+    }
+
+    #[test]
+    fn test_permissions_octal() {
+        assert_eq!(format!("{:04o}", PermissionsMask::build(0o644)), "0644");
+        assert_eq!(format!("{:04o}", PermissionsMask::build(0o4755)), "4755");
+        // File type bits from st_mode are dropped
+        assert_eq!(format!("{:04o}", PermissionsMask::build(0o100644)), "0644");
+        assert_eq!(format!("{:o}", PermissionsMask::build(0o41777)), "1777");
+    }
+}
@@ -0,0 +1,206 @@
+use std::cmp::Ordering;
+use std::os::unix::ffi::OsStrExt;
+use crate::librb::file::filemeta::{FileMetadata, TimeField};
+
+/// What a listing is ordered by, the names are the ones of ls --sort
+#[derive(EnumString, PartialEq, Clone, Copy, Debug)]
+pub enum SortKey {
+    #[strum(serialize = "name")]
+    Name,
+    #[strum(serialize = "none")]
+    None,
+    #[strum(serialize = "time")]
+    Time,
+    #[strum(serialize = "size")]
+    Size,
+    #[strum(serialize = "extension")]
+    Extension,
+    #[strum(serialize = "version")]
+    Version,
+}
+
+pub struct SortOrder {
+    pub key: SortKey,
+    /// The timestamp SortKey::Time goes by
+    pub time: TimeField,
+    pub reverse: bool,
+    pub directories_first: bool,
+}
+
+/// Byte-wise, like strcmp in the C locale
+pub fn compare_names(a: &FileMetadata, b: &FileMetadata) -> Ordering {
+    a.name.as_bytes().cmp(b.name.as_bytes())
+}
+
+/// Newest first, files without the timestamp last
+pub fn compare_times(a: &FileMetadata, b: &FileMetadata, field: TimeField) -> Ordering {
+    b.time(field).cmp(&a.time(field))
+}
+
+/// Largest first
+pub fn compare_size(a: &FileMetadata, b: &FileMetadata) -> Ordering {
+    b.size().cmp(&a.size())
+}
+
+/// Everything from the last dot on, names without one first
+fn extension(name: &[u8]) -> &[u8] {
+    match name.iter().rposition(|c| *c == b'.') {
+        Some(dot) => &name[dot..],
+        None => &[],
+    }
+}
+
+pub fn compare_extensions(a: &FileMetadata, b: &FileMetadata) -> Ordering {
+    extension(a.name.as_bytes()).cmp(extension(b.name.as_bytes()))
+}
+
+pub fn compare_versions(a: &FileMetadata, b: &FileMetadata) -> Ordering {
+    version_cmp(a.name.as_bytes(), b.name.as_bytes())
+}
+
+/// The weight of the byte at `pos` in a version comparison: the end of the string sorts before everything
+/// but ~, letters before the other non digits
+fn version_order(s: &[u8], pos: usize) -> i32 {
+    match s.get(pos) {
+        None => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => *c as i32,
+        Some(b'~') => -2,
+        Some(c) => *c as i32 + 256,
+    }
+}
+
+/// Debian's version comparison: runs of digits are compared as numbers, everything else by version_order
+fn verrevcmp(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut i, mut j) = (0, 0);
+    let is_digit = |s: &[u8], pos: usize| s.get(pos).is_some_and(u8::is_ascii_digit);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !is_digit(a, i)) || (j < b.len() && !is_digit(b, j)) {
+            let (x, y) = (version_order(a, i), version_order(b, j));
+            if x != y {
+                return x.cmp(&y);
+            }
+            i += 1;
+            j += 1;
+        }
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while is_digit(a, i) && is_digit(b, j) {
+            first_diff = first_diff.then(a[i].cmp(&b[j]));
+            i += 1;
+            j += 1;
+        }
+        if is_digit(a, i) {
+            return Ordering::Greater;
+        }
+        if is_digit(b, j) {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+/// The length of `s` without a suffix like .tar.gz, matching (\.[A-Za-z~][A-Za-z0-9~]*)*$
+fn version_prefix_len(s: &[u8]) -> usize {
+    let mut prefix_len = 0;
+    let mut i = 0;
+    while i < s.len() {
+        i += 1;
+        prefix_len = i;
+        while i + 1 < s.len() && s[i] == b'.' && (s[i + 1].is_ascii_alphabetic() || s[i + 1] == b'~') {
+            i += 2;
+            while i < s.len() && (s[i].is_ascii_alphanumeric() || s[i] == b'~') {
+                i += 1;
+            }
+        }
+    }
+    prefix_len
+}
+
+/// Natural order of file names like gnulib's filevercmp (ls -v, sort -V): file-1.9 before file-1.10,
+/// dot files first and suffixes only breaking ties
+pub fn version_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    if a.is_empty() || b.is_empty() {
+        return (!a.is_empty()).cmp(&!b.is_empty());
+    }
+    let dots = |s: &[u8]| match s {
+        b"." => 0,
+        b".." => 1,
+        [b'.', ..] => 2,
+        _ => 3,
+    };
+    let (dots_a, dots_b) = (dots(a), dots(b));
+    if dots_a != dots_b || dots_a < 2 {
+        return dots_a.cmp(&dots_b);
+    }
+    let (prefix_a, prefix_b) = (version_prefix_len(a), version_prefix_len(b));
+    let result = verrevcmp(&a[..prefix_a], &b[..prefix_b]);
+    if result != Ordering::Equal || (prefix_a == a.len() && prefix_b == b.len()) {
+        return result;
+    }
+    verrevcmp(a, b)
+}
+
+impl SortOrder {
+    fn compare(&self, a: &FileMetadata, b: &FileMetadata) -> Ordering {
+        let by_key = match self.key {
+            SortKey::Name | SortKey::None => Ordering::Equal,
+            SortKey::Time => compare_times(a, b, self.time),
+            SortKey::Size => compare_size(a, b),
+            SortKey::Extension => compare_extensions(a, b),
+            SortKey::Version => compare_versions(a, b),
+        };
+        let order = by_key.then_with(|| compare_names(a, b));
+        if self.reverse { order.reverse() } else { order }
+    }
+
+    pub fn sort(&self, entries: &mut [FileMetadata]) {
+        if self.key != SortKey::None {
+            entries.sort_by(|a, b| self.compare(a, b));
+        }
+        if self.directories_first {
+            // Stable, so the order within both groups stays
+            entries.sort_by_key(|meta| !meta.is_dir());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extension, version_cmp};
+    use std::cmp::Ordering;
+
+    fn version_sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        names.sort_by(|a, b| version_cmp(a.as_bytes(), b.as_bytes()));
+        names
+    }
+
+    #[test]
+    fn test_version_cmp() {
+        assert_eq!(version_sorted(&["file-1.10", "file-1.9", "file-1.2", "file-1"]),
+                   ["file-1", "file-1.2", "file-1.9", "file-1.10"]);
+        assert_eq!(version_sorted(&["a10", "a2", "a", "b1", ".hidden", "..", "."]),
+                   [".", "..", ".hidden", "a", "a2", "a10", "b1"]);
+        assert_eq!(version_sorted(&["foo-1.0.tar.gz", "foo-1.0~rc1.tar.gz", "foo-1.0"]),
+                   ["foo-1.0~rc1.tar.gz", "foo-1.0", "foo-1.0.tar.gz"]);
+        assert_eq!(version_cmp(b"abc", b"abc"), Ordering::Equal);
+        assert_eq!(version_cmp(b"", b"a"), Ordering::Less);
+        assert_eq!(version_cmp(b"a", b""), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_extension() {
+        assert_eq!(extension(b"a.tar.gz"), b".gz");
+        assert_eq!(extension(b"Makefile"), b"");
+        assert_eq!(extension(b".bashrc"), b".bashrc");
+    }
+}
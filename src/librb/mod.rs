@@ -1,2 +1,16 @@
+#[cfg(feature = "copy")]
+pub mod copy;
 pub mod file;
-pub mod process;
\ No newline at end of file
+#[cfg(feature = "json")]
+pub mod json;
+pub mod process;
+#[cfg(feature = "quote")]
+pub mod quote;
+#[cfg(feature = "quoting")]
+pub mod quoting;
+#[cfg(feature = "size")]
+pub mod size;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+#[cfg(feature = "time")]
+pub mod time;
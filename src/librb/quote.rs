@@ -0,0 +1,64 @@
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::os::unix::ffi::OsStrExt;
+
+/// `name` as text for display, bytes that aren't valid UTF-8 are shown as \xNN
+pub fn escape_invalid_utf8(name: &OsStr) -> Cow<'_, str> {
+    if let Some(name) = name.to_str() {
+        return Cow::Borrowed(name);
+    }
+    let mut escaped = String::new();
+    for chunk in name.as_bytes().utf8_chunks() {
+        escaped.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            // Writing into a String can't fail
+            let _ = write!(escaped, "\\x{:02x}", byte);
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// `name` as text that tells its bytes apart: like [`escape_invalid_utf8`], with backslashes doubled so \xNN is
+/// always a byte that isn't valid UTF-8 and \\ a backslash
+#[cfg(feature = "json")]
+pub fn escape_reversibly(name: &OsStr) -> Cow<'_, str> {
+    if let Some(name) = name.to_str().filter(|name| !name.contains('\\')) {
+        return Cow::Borrowed(name);
+    }
+    let mut escaped = String::new();
+    for chunk in name.as_bytes().utf8_chunks() {
+        escaped.push_str(&chunk.valid().replace('\\', "\\\\"));
+        for byte in chunk.invalid() {
+            // Writing into a String can't fail
+            let _ = write!(escaped, "\\x{:02x}", byte);
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_invalid_utf8;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn test_escape_invalid_utf8() {
+        assert_eq!(escape_invalid_utf8(OsStr::new("plain")), "plain");
+        assert_eq!(escape_invalid_utf8(OsStr::new("naïve")), "naïve");
+        assert_eq!(escape_invalid_utf8(OsStr::from_bytes(b"caf\xe9")), "caf\\xe9");
+        assert_eq!(escape_invalid_utf8(OsStr::from_bytes(b"\xff\xfe.txt")), "\\xff\\xfe.txt");
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_escape_reversibly() {
+        use super::escape_reversibly;
+        assert_eq!(escape_reversibly(OsStr::new("plain")), "plain");
+        assert_eq!(escape_reversibly(OsStr::from_bytes(b"caf\xe9")), "caf\\xe9");
+        // A name spelled like an escape isn't one
+        assert_eq!(escape_reversibly(OsStr::new("caf\\xe9")), "caf\\\\xe9");
+        assert_eq!(escape_reversibly(OsStr::from_bytes(b"a\\\xff")), "a\\\\\\xff");
+    }
+}
@@ -0,0 +1,269 @@
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+/// How names with special characters are shown, the styles of coreutils' --quoting-style
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum QuotingStyle {
+    /// As they are
+    Literal,
+    /// Quoted with '' when the shell would need it
+    Shell,
+    ShellAlways,
+    /// Like Shell, with nonprintable characters as $'\n'
+    ShellEscape,
+    ShellEscapeAlways,
+    /// Always in "" with C escapes
+    C,
+    /// C escapes without the quotes, spaces escaped too
+    Escape,
+}
+
+impl QuotingStyle {
+    /// The names of the styles, in the order of ALL
+    pub const NAMES: &'static [&'static str] = &["literal", "shell", "shell-always", "shell-escape", "shell-escape-always", "c", "escape"];
+    const ALL: [QuotingStyle; 7] = [QuotingStyle::Literal, QuotingStyle::Shell, QuotingStyle::ShellAlways, QuotingStyle::ShellEscape,
+                                    QuotingStyle::ShellEscapeAlways, QuotingStyle::C, QuotingStyle::Escape];
+
+    pub fn from_name(name: &str) -> Option<QuotingStyle> {
+        QuotingStyle::NAMES.iter().position(|style_name| *style_name == name).map(|at| QuotingStyle::ALL[at])
+    }
+}
+
+/// A piece of a name: a character, or bytes that aren't one that can be printed
+enum Unit<'a> {
+    Printable(char),
+    Nonprintable(&'a [u8]),
+}
+
+fn units(name: &OsStr) -> Vec<Unit<'_>> {
+    let mut units = Vec::new();
+    for chunk in name.as_bytes().utf8_chunks() {
+        let valid = chunk.valid();
+        for (at, c) in valid.char_indices() {
+            units.push(match c.is_control() {
+                true => Unit::Nonprintable(&valid.as_bytes()[at..at + c.len_utf8()]),
+                false => Unit::Printable(c),
+            });
+        }
+        units.extend(chunk.invalid().chunks(1).map(Unit::Nonprintable));
+    }
+    units
+}
+
+/// `bytes` the way C writes them in a string literal, \n or \ooo
+fn c_escape(bytes: &[u8]) -> String {
+    match bytes {
+        b"\x07" => "\\a".to_string(),
+        b"\x08" => "\\b".to_string(),
+        b"\x0c" => "\\f".to_string(),
+        b"\n" => "\\n".to_string(),
+        b"\r" => "\\r".to_string(),
+        b"\t" => "\\t".to_string(),
+        b"\x0b" => "\\v".to_string(),
+        _ => bytes.iter().map(|byte| format!("\\{:03o}", byte)).collect(),
+    }
+}
+
+fn push(text: &mut Vec<u8>, c: char) {
+    text.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+/// How many columns `text` takes on a terminal: like coreutils, control characters take none and bytes that
+/// aren't UTF-8 one each
+fn width(text: &[u8]) -> usize {
+    text.utf8_chunks().map(|chunk| chunk.valid().chars().filter(|c| !c.is_control()).count() + chunk.invalid().len()).sum()
+}
+
+/// Whether the shell gives `c` a meaning that quotes take away
+fn is_shell_special(c: char, at: usize, name: &[Unit]) -> bool {
+    match c {
+        ' ' | '!' | '"' | '$' | '&' | '\'' | '(' | ')' | '*' | ';' | '<' | '=' | '>' | '?' | '[' | '\\'
+        | '^' | '`' | '|' => true,
+        '#' | '~' => at == 0,
+        '{' | '}' => name.len() == 1,
+        _ => false,
+    }
+}
+
+/// Whether `c` reads the same in "" as in ''
+fn is_double_quote_compatible(c: char) -> bool {
+    c.is_alphanumeric() || !c.is_ascii() || " %+,-./:@]_'".contains(c)
+}
+
+/// A name as a quoting style shows it, bytes that aren't UTF-8 are kept as they are unless the style escapes them
+pub struct Quoted {
+    pub text: Vec<u8>,
+    /// The columns it takes on a terminal
+    pub width: usize,
+    /// Whether the style put quotes around it
+    pub quoted: bool,
+}
+
+/// How names get quoted, a style and what to change in it
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Quoting {
+    pub style: QuotingStyle,
+    /// Print ? for the characters that can't be printed, in the styles that wouldn't escape them anyway
+    pub hide_control: bool,
+    /// Characters to quote besides the style's own: the C styles escape them, the shell styles quote names with them
+    pub also: &'static str,
+}
+
+impl Quoting {
+    /// `name` as this quoting shows it
+    pub fn quote(&self, name: &OsStr) -> Quoted {
+        quote(name, *self)
+    }
+}
+
+fn quote(name: &OsStr, quoting: Quoting) -> Quoted {
+    let (text, quoted) = quote_bytes(name, quoting);
+    Quoted { width: width(&text), text, quoted }
+}
+
+fn quote_bytes(name: &OsStr, quoting: Quoting) -> (Vec<u8>, bool) {
+    let Quoting { style, hide_control, also } = quoting;
+    let units = units(name);
+    let hidden = |bytes| if hide_control { &b"?"[..] } else { bytes };
+    let mut text = Vec::new();
+    match style {
+        QuotingStyle::Literal => {
+            for unit in &units {
+                match unit {
+                    Unit::Printable(c) => push(&mut text, *c),
+                    Unit::Nonprintable(bytes) => text.extend_from_slice(hidden(bytes)),
+                }
+            }
+            (text, false)
+        }
+        QuotingStyle::C | QuotingStyle::Escape => {
+            if style == QuotingStyle::C {
+                text.push(b'"');
+            }
+            for unit in &units {
+                match unit {
+                    Unit::Printable('\\') => text.extend_from_slice(b"\\\\"),
+                    Unit::Printable('"') if style == QuotingStyle::C => text.extend_from_slice(b"\\\""),
+                    Unit::Printable(c) if also.contains(*c) => {
+                        text.push(b'\\');
+                        push(&mut text, *c);
+                    }
+                    Unit::Printable(c) => push(&mut text, *c),
+                    Unit::Nonprintable(bytes) => text.extend_from_slice(c_escape(bytes).as_bytes()),
+                }
+            }
+            if style == QuotingStyle::C {
+                text.push(b'"');
+            }
+            (text, style == QuotingStyle::C)
+        }
+        QuotingStyle::Shell | QuotingStyle::ShellAlways | QuotingStyle::ShellEscape | QuotingStyle::ShellEscapeAlways => {
+            let escape = style == QuotingStyle::ShellEscape || style == QuotingStyle::ShellEscapeAlways;
+            let always = style == QuotingStyle::ShellAlways || style == QuotingStyle::ShellEscapeAlways;
+            let needs_quotes = units.is_empty() || units.iter().enumerate().any(|(at, unit)| match unit {
+                Unit::Printable(c) => is_shell_special(*c, at, &units) || also.contains(*c),
+                // Whitespace splits words even where other control characters go through
+                Unit::Nonprintable(bytes) => escape || matches!(*bytes, b"\t" | b"\n" | b"\r"),
+            });
+            if !needs_quotes && !always {
+                for unit in &units {
+                    match unit {
+                        Unit::Printable(c) => push(&mut text, *c),
+                        Unit::Nonprintable(bytes) => text.extend_from_slice(hidden(bytes)),
+                    }
+                }
+                return (text, false);
+            }
+            // A single quote can't be in '', so "" are simpler when nothing in the name means anything there
+            let has_single_quote = units.iter().any(|unit| matches!(unit, Unit::Printable('\'')));
+            let double_quotes = has_single_quote && units.iter().all(|unit| match unit {
+                Unit::Printable(c) => is_double_quote_compatible(*c),
+                Unit::Nonprintable(_) => false,
+            });
+            if double_quotes {
+                text.push(b'"');
+                units.iter().for_each(|unit| if let Unit::Printable(c) = unit { push(&mut text, *c) });
+                text.push(b'"');
+                return (text, true);
+            }
+            text.push(b'\'');
+            // Inside a $'' part
+            let mut escaping = false;
+            for unit in &units {
+                match unit {
+                    Unit::Nonprintable(bytes) if escape => {
+                        if !escaping {
+                            text.extend_from_slice(b"'$'");
+                            escaping = true;
+                        }
+                        text.extend_from_slice(c_escape(bytes).as_bytes());
+                    }
+                    Unit::Nonprintable(bytes) => text.extend_from_slice(hidden(bytes)),
+                    Unit::Printable(c) => {
+                        if escaping {
+                            text.extend_from_slice(b"''");
+                            escaping = false;
+                        }
+                        match c {
+                            '\'' => text.extend_from_slice(b"'\\''"),
+                            c => push(&mut text, *c),
+                        }
+                    }
+                }
+            }
+            text.push(b'\'');
+            (text, true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Quoting, QuotingStyle};
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn test_quote() {
+        let quoted = |name: &[u8], style| String::from_utf8(Quoting { style, hide_control: false, also: "" }.quote(OsStr::from_bytes(name)).text).unwrap();
+        let names: [&[u8]; 9] = [b"plain", b"a b", b"it's", b"a\"b", b"nl\nx", b"\ns", b"#hash", b"x~y", b"back\\slash"];
+        let expected = [
+            (QuotingStyle::Shell, ["plain", "'a b'", "\"it's\"", "'a\"b'", "'nl\nx'", "'\ns'", "'#hash'", "x~y", "'back\\slash'"]),
+            (QuotingStyle::ShellAlways, ["'plain'", "'a b'", "\"it's\"", "'a\"b'", "'nl\nx'", "'\ns'", "'#hash'", "'x~y'", "'back\\slash'"]),
+            (QuotingStyle::ShellEscape, ["plain", "'a b'", "\"it's\"", "'a\"b'", "'nl'$'\\n''x'", "''$'\\n''s'", "'#hash'", "x~y", "'back\\slash'"]),
+            (QuotingStyle::C, ["\"plain\"", "\"a b\"", "\"it's\"", "\"a\\\"b\"", "\"nl\\nx\"", "\"\\ns\"", "\"#hash\"", "\"x~y\"", "\"back\\\\slash\""]),
+            (QuotingStyle::Escape, ["plain", "a b", "it's", "a\"b", "nl\\nx", "\\ns", "#hash", "x~y", "back\\\\slash"]),
+        ];
+        for (style, expected) in expected.iter() {
+            for (name, expected) in names.iter().zip(expected.iter()) {
+                assert_eq!(quoted(name, *style), *expected, "{:?} {:?}", style, name);
+            }
+        }
+        assert_eq!(quoted(b"end\n", QuotingStyle::ShellEscape), "'end'$'\\n'");
+        assert_eq!(quoted(b"it's $x", QuotingStyle::Shell), "'it'\\''s $x'");
+        assert_eq!(quoted(b"caf\xe9\x01", QuotingStyle::Escape), "caf\\351\\001");
+        assert_eq!(quoted(b"{", QuotingStyle::Shell), "'{'");
+        assert_eq!(quoted(b"a{b", QuotingStyle::Shell), "a{b");
+        assert!(!Quoting { style: QuotingStyle::ShellEscape, hide_control: false, also: "" }.quote(OsStr::new("plain")).quoted);
+        let hiding = |style| Quoting { style, hide_control: true, also: "" };
+        assert_eq!(hiding(QuotingStyle::Literal).quote(OsStr::new("tab\there\x01")).text, b"tab?here?");
+        assert_eq!(hiding(QuotingStyle::Shell).quote(OsStr::new("a b\x01")).text, b"'a b?'");
+        let also = |style| Quoting { style, hide_control: false, also: " :" };
+        assert_eq!(also(QuotingStyle::Escape).quote(OsStr::new("a b:c")).text, b"a\\ b\\:c");
+        assert_eq!(also(QuotingStyle::Shell).quote(OsStr::new("a:b")).text, b"'a:b'");
+        assert_eq!(QuotingStyle::from_name("shell-escape"), Some(QuotingStyle::ShellEscape));
+    }
+
+    #[test]
+    fn test_quote_non_utf8() {
+        // Like coreutils, the styles that don't escape write bytes that aren't UTF-8 as they are
+        let quote = |name: &[u8], style| Quoting { style, hide_control: false, also: "" }.quote(OsStr::from_bytes(name));
+        assert_eq!(quote(b"a\xff", QuotingStyle::Literal).text, b"a\xff");
+        assert_eq!(quote(b"a\xff", QuotingStyle::Literal).width, 2);
+        assert_eq!(quote(b"a \xff", QuotingStyle::Shell).text, b"'a \xff'");
+        assert_eq!(quote(b"a\xff", QuotingStyle::ShellEscape).text, b"'a'$'\\377'");
+        assert_eq!(quote(b"a\xff", QuotingStyle::C).text, b"\"a\\377\"");
+        assert_eq!(Quoting { style: QuotingStyle::Literal, hide_control: true, also: "" }.quote(OsStr::from_bytes(b"a\xff")).text, b"a?");
+        assert_eq!(quote(b"tab\tx", QuotingStyle::Literal).width, 4);
+    }
+}
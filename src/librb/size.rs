@@ -0,0 +1,199 @@
+/// Unit letters of the powers of the base, K for the first
+const POWER_LETTERS: &[u8] = b"KMGTPEZY";
+
+/// How sizes are printed, the options of coreutils' --block-size and -h. Sizes are always rounded up,
+/// so a nonempty file never shows as 0.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SizeFormat {
+    /// The unit sizes are counted in
+    pub block_size: u64,
+    /// Pick the largest unit the size has at least one of, like 1.5K
+    autoscale: bool,
+    /// Print the unit letter after the number
+    show_unit: bool,
+    /// Units are powers of 1024, otherwise of 1000
+    base_1024: bool,
+    /// Append B, or iB for powers of 1024, after the unit letter
+    show_bytes: bool,
+}
+
+impl SizeFormat {
+    /// Plain byte counts
+    pub const BYTES: SizeFormat = SizeFormat::blocks(1);
+    /// -h, powers of 1024
+    pub const HUMAN: SizeFormat = SizeFormat { block_size: 1, autoscale: true, show_unit: true, base_1024: true, show_bytes: false };
+    /// --si, powers of 1000
+    pub const SI: SizeFormat = SizeFormat { block_size: 1, autoscale: true, show_unit: true, base_1024: false, show_bytes: false };
+
+    /// Counts of `block_size` bytes without a unit
+    pub const fn blocks(block_size: u64) -> SizeFormat {
+        SizeFormat { block_size, autoscale: false, show_unit: false, base_1024: true, show_bytes: false }
+    }
+
+    /// A --block-size argument: "human-readable", "si", or an optional number followed by an optional unit
+    /// (K, M, G, ... in powers of 1024, KB, MB, ... in powers of 1000, KiB, MiB, ...). A unit without a
+    /// number is printed after the sizes.
+    pub fn parse(spec: &str) -> Result<SizeFormat, String> {
+        let invalid = || format!("invalid --block-size argument '{}'", spec);
+        match spec {
+            "human-readable" => return Ok(SizeFormat::HUMAN),
+            "si" => return Ok(SizeFormat::SI),
+            "" => return Err(invalid()),
+            _ => {}
+        }
+        let digits = spec.find(|c: char| !c.is_ascii_digit()).unwrap_or(spec.len());
+        let number = match digits {
+            0 => 1,
+            _ => spec[..digits].parse::<u64>().map_err(|_| invalid())?,
+        };
+        let unit = &spec[digits..];
+        let (exponent, rest) = match unit.chars().next() {
+            None => (0, ""),
+            Some(letter) => match POWER_LETTERS.iter().position(|l| *l == letter.to_ascii_uppercase() as u8) {
+                Some(power) => (power as u32 + 1, &unit[1..]),
+                None => return Err(invalid()),
+            },
+        };
+        let (base_1024, show_bytes) = match rest {
+            "" => (true, false),
+            "B" | "D" => (false, true),
+            "iB" => (true, true),
+            _ => return Err(invalid()),
+        };
+        let base: u64 = if base_1024 { 1024 } else { 1000 };
+        let block_size = base.checked_pow(exponent).and_then(|unit| unit.checked_mul(number)).filter(|size| *size > 0)
+            .ok_or_else(invalid)?;
+        let show_unit = digits == 0;
+        Ok(SizeFormat { block_size, autoscale: false, show_unit, base_1024, show_bytes: show_unit && show_bytes })
+    }
+
+    fn base(&self) -> u64 {
+        if self.base_1024 { 1024 } else { 1000 }
+    }
+
+    /// `amount` units of `unit` bytes, e.g. 3 blocks of 512 bytes. Ported from gnulib's human_readable, which
+    /// is what keeps the rounding of the tenths identical to coreutils.
+    pub fn format(&self, amount: u64, unit: u64) -> String {
+        let base = self.base();
+        let total = amount as u128 * unit as u128;
+        let to = self.block_size as u128;
+        // amt and how much of the next unit is left over: the tenths, and whether the rest is more than half
+        let mut amt = total / to;
+        let r10 = (total % to) * 10;
+        let mut tenths = (r10 / to) as u64;
+        let r2 = (r10 % to) * 2;
+        let mut rounding = match r2.cmp(&to) {
+            std::cmp::Ordering::Less => (r2 > 0) as u64,
+            std::cmp::Ordering::Equal => 2,
+            std::cmp::Ordering::Greater => 3,
+        };
+        let mut exponent: usize = 0;
+        let mut point = String::new();
+
+        if self.autoscale && amt >= base as u128 {
+            while amt >= base as u128 && exponent < POWER_LETTERS.len() {
+                let r10 = (amt % base as u128) as u64 * 10 + tenths;
+                let r2 = (r10 % base) * 2 + (rounding >> 1);
+                amt /= base as u128;
+                tenths = r10 / base;
+                rounding = match r2 < base {
+                    true => (r2 + rounding != 0) as u64,
+                    false => 2 + (base < r2 + rounding) as u64,
+                };
+                exponent += 1;
+            }
+            if amt < 10 {
+                if rounding > 0 {
+                    tenths += 1;
+                    rounding = 0;
+                    if tenths == 10 {
+                        amt += 1;
+                        tenths = 0;
+                    }
+                }
+                if amt < 10 {
+                    point = format!(".{}", tenths);
+                    tenths = 0;
+                    rounding = 0;
+                }
+            }
+        }
+        if tenths + rounding > 0 {
+            amt += 1;
+            if self.autoscale && amt == base as u128 && exponent < POWER_LETTERS.len() {
+                exponent += 1;
+                amt = 1;
+                point = ".0".to_string();
+            }
+        }
+
+        let mut formatted = format!("{}{}", amt, point);
+        if self.show_unit {
+            if !self.autoscale {
+                // The unit is the block size itself
+                let mut power = 1;
+                while power < self.block_size && exponent < POWER_LETTERS.len() {
+                    power = power.saturating_mul(base);
+                    exponent += 1;
+                }
+            }
+            if exponent > 0 {
+                formatted.push(match (self.base_1024, exponent) {
+                    (false, 1) => 'k',
+                    _ => POWER_LETTERS[exponent - 1] as char,
+                });
+            }
+            if self.show_bytes {
+                if self.base_1024 && exponent > 0 {
+                    formatted.push('i');
+                }
+                formatted.push('B');
+            }
+        }
+        formatted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SizeFormat;
+
+    #[test]
+    fn test_human() {
+        let human = |size| SizeFormat::HUMAN.format(size, 1);
+        assert_eq!(human(0), "0");
+        assert_eq!(human(1023), "1023");
+        assert_eq!(human(1024), "1.0K");
+        assert_eq!(human(1025), "1.1K");
+        assert_eq!(human(1536), "1.5K");
+        assert_eq!(human(10239), "10K");
+        assert_eq!(human(99999), "98K");
+        assert_eq!(human(1048575), "1.0M");
+        assert_eq!(human(1048577), "1.1M");
+        assert_eq!(human(5 << 30), "5.0G");
+        assert_eq!(SizeFormat::HUMAN.format(2304, 512), "1.2M");
+        let si = |size| SizeFormat::SI.format(size, 1);
+        assert_eq!(si(1023), "1.1k");
+        assert_eq!(si(99999), "100k");
+        assert_eq!(si(999999), "1.0M");
+        assert_eq!(si(5 << 30), "5.4G");
+    }
+
+    #[test]
+    fn test_block_size() {
+        let format = |spec, size, unit| SizeFormat::parse(spec).unwrap().format(size, unit);
+        assert_eq!(format("K", 1023, 1), "1K");
+        assert_eq!(format("K", 8, 512), "4K");
+        assert_eq!(format("1K", 1048577, 1), "1025");
+        assert_eq!(format("KB", 1023, 1), "2kB");
+        assert_eq!(format("KiB", 5 << 30, 1), "5242880KiB");
+        assert_eq!(format("M", 5 << 30, 1), "5120M");
+        assert_eq!(format("2K", 8, 512), "2");
+        assert_eq!(format("human-readable", 1536, 1), "1.5K");
+        assert_eq!(SizeFormat::parse("1024"), Ok(SizeFormat::blocks(1024)));
+        assert_eq!(SizeFormat::BYTES.format(12345, 1), "12345");
+        for spec in ["", "0", "x", "Kx", "1.5K", "99999999999999999999", "Y"].iter() {
+            assert_eq!(SizeFormat::parse(spec), Err(format!("invalid --block-size argument '{}'", spec)));
+        }
+    }
+}
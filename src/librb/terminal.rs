@@ -0,0 +1,50 @@
+use std::mem::MaybeUninit;
+
+/// What an applet knows about the terminal its output goes to, layouts and colors depend on it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Terminal {
+    pub is_tty: bool,
+    /// Columns of the terminal, when it reports them
+    pub width: Option<usize>,
+}
+
+impl Terminal {
+    /// Output that isn't a terminal, what tests render for
+    #[cfg(test)]
+    pub const NONE: Terminal = Terminal { is_tty: false, width: None };
+
+    pub fn stdout() -> Terminal {
+        Terminal { is_tty: atty::is(atty::Stream::Stdout), width: stdout_width() }
+    }
+}
+
+/// TIOCGWINSZ of stdout
+fn stdout_width() -> Option<usize> {
+    let mut size = MaybeUninit::<libc::winsize>::zeroed();
+    // The ioctl only fills in the winsize it is given
+    let size = unsafe {
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, size.as_mut_ptr()) != 0 {
+            return None;
+        }
+        size.assume_init()
+    };
+    Some(size.ws_col as usize).filter(|width| *width > 0)
+}
+
+/// The width COLUMNS asks for, if it is a valid one
+pub fn columns_from_env() -> Option<usize> {
+    std::env::var("COLUMNS").ok()?.parse().ok().filter(|width| *width > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Terminal;
+
+    #[test]
+    fn test_terminal() {
+        let stdout = Terminal::stdout();
+        if !stdout.is_tty {
+            assert_eq!(stdout.width, None);
+        }
+    }
+}
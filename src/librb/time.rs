@@ -0,0 +1,123 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use std::borrow::Cow;
+use std::time::{Duration, SystemTime};
+
+/// Times longer ago than this (or in the future) are old, the styles show them with the year
+const SIX_MONTHS: Duration = Duration::from_secs(31_556_952 / 2);
+
+/// `time` in the local time zone as strftime(3) formats it, with %N for the nanoseconds like GNU date.
+/// Conversions chrono doesn't know are printed as they are, like glibc does.
+pub fn strftime(time: SystemTime, format: &str) -> String {
+    let mut translated = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            translated.push(c);
+            continue;
+        }
+        // Flags and widths like %-d or %3f are part of the conversion
+        let mut conversion = String::from("%");
+        for c in chars.by_ref() {
+            conversion.push(c);
+            if !(c.is_ascii_digit() || "-_0^#:.".contains(c)) {
+                break;
+            }
+        }
+        match conversion.as_str() {
+            "%N" => translated += "%9f",
+            _ if StrftimeItems::new(&conversion).any(|item| item == Item::Error) => translated += &conversion.replace('%', "%%"),
+            _ => translated += &conversion,
+        }
+    }
+    DateTime::<Local>::from(time).format(&translated).to_string()
+}
+
+/// How long listings show times, the --time-style of coreutils
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeStyle {
+    /// The strftime formats for times of the last six months and for the others
+    recent: Cow<'static, str>,
+    old: Cow<'static, str>,
+}
+
+impl TimeStyle {
+    /// What ls shows in the C locale
+    pub const LOCALE: TimeStyle = TimeStyle { recent: Cow::Borrowed("%b %e %H:%M"), old: Cow::Borrowed("%b %e  %Y") };
+    pub const FULL_ISO: TimeStyle = TimeStyle::fixed("%Y-%m-%d %H:%M:%S.%N %z");
+    pub const LONG_ISO: TimeStyle = TimeStyle::fixed("%Y-%m-%d %H:%M");
+    pub const ISO: TimeStyle = TimeStyle { recent: Cow::Borrowed("%m-%d %H:%M"), old: Cow::Borrowed("%Y-%m-%d ") };
+
+    /// The same format for all times
+    const fn fixed(format: &'static str) -> TimeStyle {
+        TimeStyle { recent: Cow::Borrowed(format), old: Cow::Borrowed(format) }
+    }
+
+    /// full-iso, long-iso, iso, locale, or +FORMAT where a FORMAT for old times can come before a newline and
+    /// one for recent times. The posix- prefix asks for the style only outside the POSIX locale, which is the
+    /// only one there is here.
+    pub fn parse(spec: &str) -> Result<TimeStyle, String> {
+        if let Some(format) = spec.strip_prefix('+') {
+            return match format.split('\n').collect::<Vec<_>>()[..] {
+                [format] => Ok(TimeStyle { recent: format.to_string().into(), old: format.to_string().into() }),
+                [old, recent] => Ok(TimeStyle { recent: recent.to_string().into(), old: old.to_string().into() }),
+                _ => Err(format!("invalid time style format '{}'", format)),
+            };
+        }
+        match spec {
+            "full-iso" => Ok(TimeStyle::FULL_ISO),
+            "long-iso" => Ok(TimeStyle::LONG_ISO),
+            "iso" => Ok(TimeStyle::ISO),
+            "locale" => Ok(TimeStyle::LOCALE),
+            _ if spec.starts_with("posix-") => Ok(TimeStyle::LOCALE),
+            _ => Err(format!("invalid argument '{}' for 'time style'", spec)),
+        }
+    }
+
+    /// `time` the way this style shows it `now`
+    pub fn format(&self, time: SystemTime, now: SystemTime) -> String {
+        let recent = now.duration_since(time).is_ok_and(|age| age < SIX_MONTHS);
+        strftime(time, if recent { &self.recent } else { &self.old })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strftime, TimeStyle};
+    use std::process::Command;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    fn date(format: &str, epoch: &str) -> String {
+        let out = Command::new("date").arg("-d").arg(format!("@{}", epoch)).arg(format!("+{}", format)).output().unwrap();
+        String::from_utf8(out.stdout).unwrap().trim_end_matches('\n').to_string()
+    }
+
+    #[test]
+    fn test_strftime() {
+        let time = UNIX_EPOCH + Duration::new(981_173_106, 5);
+        for format in ["%Y-%m-%d %H:%M:%S.%N %z", "%b %e  %Y", "%-d/%_m %%N %s", "%j %a %A %T"].iter() {
+            assert_eq!(strftime(time, format), date(format, "981173106.000000005"));
+        }
+        // Unknown conversions stay as they are
+        assert_eq!(strftime(time, "%Q %"), "%Q %");
+    }
+
+    #[test]
+    fn test_time_style() {
+        let now = SystemTime::now();
+        let recent = now - Duration::from_secs(3600);
+        let old = now - Duration::from_secs(400 * 24 * 3600);
+        let epoch = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap().as_secs().to_string();
+        for (spec, recent_format, old_format) in [("locale", "%b %e %H:%M", "%b %e  %Y"), ("long-iso", "%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M"),
+                                                  ("iso", "%m-%d %H:%M", "%Y-%m-%d "), ("+%Y", "%Y", "%Y"), ("+old %Y\nnew %H", "new %H", "old %Y"),
+                                                  ("posix-iso", "%b %e %H:%M", "%b %e  %Y")].iter() {
+            let style = TimeStyle::parse(spec).unwrap();
+            assert_eq!(style.format(recent, now), date(recent_format, &epoch(recent)), "{}", spec);
+            assert_eq!(style.format(old, now), date(old_format, &epoch(old)), "{}", spec);
+        }
+        // The future is never recent
+        assert_eq!(TimeStyle::ISO.format(now + Duration::from_secs(3600), now).len(), "2001-01-01 ".len());
+        assert_eq!(TimeStyle::parse("bogus"), Err("invalid argument 'bogus' for 'time style'".to_string()));
+        assert!(TimeStyle::parse("+a\nb\nc").is_err());
+    }
+}
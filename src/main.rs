@@ -1,56 +1,219 @@
-mod librb;
-mod applets;
-mod core;
+use clap::{App, Arg, ArgMatches, ErrorKind};
+use rustybox::applets::{APPLETS, find_applet};
+use rustybox::core::applet::Applet;
+use rustybox::core::args::add_generic_info;
+use rustybox::core::error::{AppletError, report, EXIT_USAGE};
+use rustybox::core::install::{install_applets, InstallOptions, INSTALL_FLAG};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
 
-use crate::applets::ls::ls_main;
-use crate::applets::touch::{touch_main};
-use crate::applets::true_app::{true_main};
-use clap::App;
-use crate::core::args::add_generic_info;
-use crate::applets::env::env_main;
-use crate::applets::cat::cat_main;
-use crate::applets::sleep::sleep_main;
+fn get_app() -> App<'static, 'static> {
+    APPLETS.iter().fold(add_generic_info(App::new("rustybox")), |app, applet| app.subcommand(applet.subcommand()))
+        .arg(
+            Arg::with_name("list").long("--list").help("list the available applets")
+        )
+}
 
+/// The applet name rustybox was invoked as, e.g. "ls" for /bin/ls -> rustybox
+fn applet_name(argv0: &OsStr) -> Option<&str> {
+    Path::new(argv0).file_name()?.to_str()
+}
 
-extern crate chrono;
-extern crate strum;
-#[macro_use] extern crate strum_macros;
-#[macro_use]
-extern crate bitflags;
+fn list_applets(writer: &mut dyn Write) -> io::Result<()> {
+    for applet in APPLETS.iter() {
+        writeln!(writer, "{}", applet.name())?;
+    }
+    Ok(())
+}
 
-extern crate num_enum;
+/// Report `err` on behalf of `name` and turn it into an exit status
+fn exit_status(name: &str, result: Result<(), AppletError>, stderr: &mut dyn Write) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            if err.message().is_some() {
+                report(stderr, name, &err);
+            }
+            err.status()
+        }
+    }
+}
 
-fn get_app() -> App<'static, 'static> {
-    add_generic_info(App::new("rustybox"))
-        .subcommand(applets::ls::subcommand())
-        .subcommand(applets::touch::subcommand())
-        .subcommand(applets::env::subcommand())
-        .subcommand(applets::cat::subcommand())
-        .subcommand(applets::sleep::subcommand())
-        .subcommand(applets::true_app::subcommand())
+/// --help and --version are "errors" as well, only those go to stdout and succeed
+fn clap_exit_status(err: clap::Error, stderr: &mut dyn Write) -> i32 {
+    match err.kind {
+        ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => {
+            // clap already printed the version itself, without the trailing newline
+            println!("{}", err.message);
+            0
+        },
+        _ => {
+            let _ = writeln!(stderr, "{}", err.message);
+            EXIT_USAGE
+        }
+    }
+}
 
+fn run_applet(applet: &dyn Applet, args: &ArgMatches, stderr: &mut dyn Write) -> i32 {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let fds = (stdin.as_raw_fd(), stdout.as_raw_fd());
+    let mut result = applet.main_with_fds(args, &mut stdin.lock(), &mut stdout, stderr, fds);
+    if result.is_ok() {
+        result = stdout.flush().map_err(AppletError::from);
+    }
+    exit_status(applet.name(), result, stderr)
+}
+
+fn install(args: &[OsString], stderr: &mut dyn Write) -> i32 {
+    let matches = match rustybox::core::install::subcommand().get_matches_from_safe(args) {
+        Ok(matches) => matches,
+        Err(e) => return clap_exit_status(e, stderr),
+    };
+    let result = std::env::current_exe().map_err(AppletError::from).and_then(|exe| {
+        // OK because argument is required
+        let dir = matches.value_of_os("dir").unwrap();
+        install_applets(APPLETS, &exe, Path::new(dir), &InstallOptions::build(&matches))
+    });
+    exit_status("install", result, stderr)
 }
 
-fn main() -> Result<(), String> {
+fn run(args: Vec<OsString>, stderr: &mut dyn Write) -> i32 {
+    if args.get(1).map(OsString::as_os_str) == Some(OsStr::new(INSTALL_FLAG)) {
+        return install(&args[1..], stderr);
+    }
+
+    // Invoked through a link named after an applet, or as rustybox APPLET ARGS, the applet parses its arguments alone
+    let applet = args.first().and_then(|argv0| applet_name(argv0)).and_then(find_applet).map(|applet| (applet, 0))
+        .or_else(|| args.get(1).and_then(|name| name.to_str()).and_then(find_applet).map(|applet| (applet, 1)));
+    if let Some((applet, start)) = applet {
+        return match applet.get_matches(args[start..].to_vec()) {
+            Ok(matches) => run_applet(applet, &matches, stderr),
+            Err(e) => clap_exit_status(e, stderr),
+        };
+    }
+
     let mut app = get_app();
-    let args = app.get_matches_from_safe_borrow(std::env::args());
-    if let Ok(args) =  args {
-        let (cmd, args) = args.subcommand();
-        match cmd {
-            "touch" => touch_main(args),
-            "ls" => ls_main(args),
-            "env" => env_main(args),
-            "cat" => cat_main(args),
-            "sleep" => sleep_main(args),
-            "true" => true_main(args),
-            "" => { app.print_long_help(); println!(""); Ok(()) },
-            cmd => {
-                app.print_long_help().or(Err("Failed to print help"))?;
-                Err(format!("Invalid Command {}", cmd).to_string())
-            },
+    let matches = match app.get_matches_from_safe_borrow(args) {
+        Ok(matches) => matches,
+        Err(e) => return clap_exit_status(e, stderr),
+    };
+    if matches.is_present("list") {
+        return exit_status("rustybox", list_applets(&mut io::stdout()).map_err(AppletError::from), stderr);
+    }
+    match matches.subcommand() {
+        (cmd, Some(args)) => match find_applet(cmd) {
+            Some(applet) => run_applet(applet, args, stderr),
+            None => exit_status("rustybox", Err(AppletError::usage(format!("Invalid Command {}", cmd))), stderr),
+        },
+        _ => {
+            let mut stdout = io::stdout();
+            let result = app.write_long_help(&mut stdout)
+                .map_err(|e| AppletError::failure(e.message))
+                .and_then(|_| writeln!(stdout).map_err(AppletError::from));
+            exit_status("rustybox", result, stderr)
+        },
+    }
+}
+
+fn main() {
+    rustybox::core::pipe::restore_default_sigpipe();
+    let status = run(std::env::args_os().collect(), &mut io::stderr());
+    std::process::exit(status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{applet_name, list_applets, get_app};
+    use rustybox::applets::APPLETS;
+    use rustybox::core::error::EXIT_USAGE;
+    use std::ffi::{OsStr, OsString};
+    use std::str;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    /// Run rustybox with `argv`, returning the exit status and what was written to stderr
+    fn run(argv: &[&str]) -> (i32, String) {
+        let mut stderr: Vec<u8> = Vec::new();
+        let status = super::run(args(argv), &mut stderr);
+        (status, String::from_utf8(stderr).unwrap())
+    }
+
+    #[test]
+    fn test_applet_name() {
+        assert_eq!(applet_name(OsStr::new("/bin/ls")), Some("ls"));
+        assert_eq!(applet_name(OsStr::new("cat")), Some("cat"));
+        assert_eq!(applet_name(OsStr::new("./target/debug/rustybox")), Some("rustybox"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "true", feature = "sleep"))]
+    fn test_dispatch_argv0() {
+        assert_eq!(run(&["/usr/bin/true"]), (0, "".to_string()));
+        assert_eq!(run(&["/bin/sleep", "1k"]), (1, "rustybox: sleep: Invalid char k in arg 1k\n".to_string()));
+    }
+
+    #[test]
+    #[cfg(all(feature = "true", feature = "sleep"))]
+    fn test_dispatch_subcommand() {
+        assert_eq!(run(&["rustybox", "true"]), (0, "".to_string()));
+        assert_eq!(run(&["/bin/rustybox", "sleep", "1k"]), (1, "rustybox: sleep: Invalid char k in arg 1k\n".to_string()));
+    }
+
+    #[test]
+    #[cfg(feature = "touch")]
+    fn test_dispatch_both_styles_touch() {
+        use std::path::Path;
+        use std::process::Command;
+
+        let name = "/tmp/rustybox-main-test-touch";
+        for argv in [vec!["/bin/touch", name], vec!["rustybox", "touch", name]].iter() {
+            Command::new("sh")
+                .arg("-c")
+                .arg(format!("rm -f {name}", name=name))
+                .output()
+                .expect("failed to execute process");
+            assert_eq!(run(argv).0, 0);
+            assert!(Path::new(name).exists());
         }
-    } else {
-        println!("{}", args.unwrap_err().message);
-        Ok(())
+    }
+
+    #[test]
+    fn test_dispatch_no_applet() {
+        assert_eq!(run(&["rustybox", "--list"]).0, 0);
+        assert_eq!(run(&["/bin/not-an-applet"]).0, 0);
+        assert_eq!(run(&["rustybox", "--bogus"]).0, EXIT_USAGE);
+        assert_eq!(run(&["rustybox", "--install"]).0, EXIT_USAGE);
+    }
+
+    #[test]
+    #[cfg(feature = "true")]
+    fn test_usage_errors() {
+        let (status, stderr) = run(&["/bin/true", "--bogus"]);
+        assert_eq!(status, EXIT_USAGE);
+        assert!(stderr.starts_with("error: Found argument '--bogus'"));
+        assert_eq!(run(&["/bin/true", "--help"]), (0, "".to_string()));
+    }
+
+    #[test]
+    fn test_every_applet_is_a_subcommand() {
+        let app = get_app();
+        for applet in APPLETS.iter() {
+            assert!(app.p.subcommands.iter().any(|cmd| cmd.get_name() == applet.name()));
+        }
+    }
+
+    #[test]
+    fn test_list_applets() {
+        let mut output: Vec<u8> = Vec::new();
+        list_applets(&mut output).unwrap();
+        let names: Vec<&str> = APPLETS.iter().map(|applet| applet.name()).collect();
+        assert_eq!(str::from_utf8(&output).unwrap().lines().collect::<Vec<&str>>(), names);
     }
 }